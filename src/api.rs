@@ -0,0 +1,363 @@
+//! Opt-in HTTP API on localhost, for browser extensions, dashboards and scripts.
+//!
+//! `GET /status`, `POST /toggle`, `POST /break`, `POST /reset`, `GET /stats?from=&to=` with
+//! `YYYY-MM-DD` dates, and `GET /events`, a Server-Sent Events stream of the status. Requests
+//! from web pages of other hosts are refused, so a website can't drive the timer, and so are
+//! requests naming another `Host`, which keeps DNS rebinding pages from reading the stats.
+use crate::timer::{mode_key, TimerMode};
+use crate::{day_work, export, storage, today, DayStat, TimerMsg};
+use anyhow::bail;
+use lazy_static::lazy_static;
+use serde_derive::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// How often an idle event stream gets a comment, which also notices closed connections
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Serialize)]
+struct Status {
+    /// `work`, `break`, `paused` or `stopped`
+    mode: &'static str,
+    /// Worked so far in a session, or left of a break
+    seconds: u32,
+    /// `seconds` as the clock shows it
+    time: String,
+    /// Work saved for today, without the running session
+    today_work_second: u32,
+}
+
+lazy_static! {
+    /// The latest status with a version counting its changes
+    static ref STATUS: Mutex<(u64, Status)> = Mutex::new((
+        1,
+        Status {
+            mode: "stopped",
+            seconds: 0,
+            time: String::new(),
+            today_work_second: 0,
+        }
+    ));
+    static ref CHANGED: Condvar = Condvar::new();
+}
+
+/// Publishes the state of the timer to `/status` and `/events`
+pub fn update(mode: &TimerMode, seconds: u32, time: &str) {
+    let mut status = STATUS.lock().unwrap();
+    let mode = mode_key(mode);
+    if status.1.mode == mode && status.1.seconds == seconds {
+        return;
+    }
+    status.0 += 1;
+    status.1.mode = mode;
+    status.1.seconds = seconds;
+    status.1.time = time.to_string();
+    CHANGED.notify_all();
+}
+
+fn status_json() -> String {
+    let status = Status {
+        today_work_second: day_work(today()),
+        ..STATUS.lock().unwrap().1.clone()
+    };
+    serde_json::to_string(&status).unwrap()
+}
+
+fn error_json(message: &str) -> String {
+    json!({ "error": message }).to_string()
+}
+
+/// Serves the API on `127.0.0.1:port`, `command` gets the timer messages of the POSTs
+pub fn start(port: u16, command: impl Fn(TimerMsg) + Send + Sync + 'static) {
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("flowtime: failed to start the API on port {}: {}", port, e);
+            return;
+        }
+    };
+    let command: Arc<dyn Fn(TimerMsg) + Send + Sync> = Arc::new(command);
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let command = command.clone();
+            // event streams stay open, so every connection gets its own thread
+            std::thread::spawn(move || {
+                if let Err(e) = handle(&stream, port, command.as_ref()) {
+                    eprintln!("flowtime: API request failed: {}", e);
+                }
+            });
+        }
+    });
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    host: Option<String>,
+    origin: Option<String>,
+}
+
+fn read_request(stream: &TcpStream) -> anyhow::Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+        _ => bail!("malformed request line {:?}", line),
+    };
+    let mut host = None;
+    let mut origin = None;
+    let mut length = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            match name.trim().to_ascii_lowercase().as_str() {
+                "host" => host = Some(value.trim().to_string()),
+                "origin" => origin = Some(value.trim().to_string()),
+                "content-length" => length = value.trim().parse().unwrap_or(0),
+                _ => (),
+            }
+        }
+    }
+    // bodies aren't used, but have to be read before answering
+    std::io::copy(&mut reader.take(length), &mut std::io::sink())?;
+    let (path, query) = target.split_once('?').unwrap_or((target.as_str(), ""));
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        query: query
+            .split('&')
+            .filter_map(|x| x.split_once('='))
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+        host,
+        origin,
+    })
+}
+
+/// Whether `host` names this machine, with or without a port
+fn local(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(x) => x.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    matches!(name, "localhost" | "127.0.0.1" | "::1")
+}
+
+/// Pages of this machine, or browser extensions, which send their own scheme
+fn trusted(origin: &str) -> bool {
+    if let Some(rest) = origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"))
+    {
+        return local(rest.split('/').next().unwrap_or_default());
+    }
+    // sandboxed frames and file pages send `null`, any web page can do that
+    [
+        "chrome-extension://",
+        "moz-extension://",
+        "safari-web-extension://",
+    ]
+    .iter()
+    .any(|x| origin.starts_with(x))
+}
+
+/// Whether the request was addressed to this server rather than to a rebound domain name
+fn addressed(host: Option<&str>, port: u16) -> bool {
+    ["localhost", "127.0.0.1", "[::1]"]
+        .iter()
+        .any(|name| host == Some(format!("{name}:{port}").as_str()))
+}
+
+fn respond(mut stream: &TcpStream, status: &str, headers: &str, body: &str) -> anyhow::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n{headers}\r\n{body}",
+        body.len()
+    )?;
+    Ok(())
+}
+
+fn handle(
+    stream: &TcpStream,
+    port: u16,
+    command: &(dyn Fn(TimerMsg) + Send + Sync),
+) -> anyhow::Result<()> {
+    let request = read_request(stream)?;
+    if !addressed(request.host.as_deref(), port) {
+        return respond(stream, "403 Forbidden", "", &error_json("unknown host"));
+    }
+    let cors = match &request.origin {
+        Some(origin) if !trusted(origin) => {
+            return respond(stream, "403 Forbidden", "", &error_json("untrusted origin"))
+        }
+        Some(origin) => format!("Access-Control-Allow-Origin: {origin}\r\n"),
+        None => String::new(),
+    };
+    let accept = |msg: TimerMsg| {
+        let body = json!({ "accepted": request.path.trim_start_matches('/') }).to_string();
+        command(msg);
+        respond(stream, "202 Accepted", &cors, &body)
+    };
+    match (request.method.as_str(), request.path.as_str()) {
+        ("OPTIONS", _) => respond(
+            stream,
+            "204 No Content",
+            &format!("{cors}Access-Control-Allow-Methods: GET, POST\r\n"),
+            "",
+        ),
+        ("GET", "/status") => respond(stream, "200 OK", &cors, &status_json()),
+        ("POST", "/toggle") => accept(TimerMsg::ToggleFlowTime),
+        ("POST", "/break") => accept(TimerMsg::ToggleBreak),
+        ("POST", "/reset") => accept(TimerMsg::ResetSession),
+        ("GET", "/stats") => match stats_json(&request.query) {
+            Ok(body) => respond(stream, "200 OK", &cors, &body),
+            Err(e) => respond(
+                stream,
+                "400 Bad Request",
+                &cors,
+                &error_json(&e.to_string()),
+            ),
+        },
+        ("GET", "/events") => events(stream, &cors),
+        (_, "/status" | "/toggle" | "/break" | "/reset" | "/stats" | "/events") => respond(
+            stream,
+            "405 Method Not Allowed",
+            &cors,
+            &error_json("method not allowed"),
+        ),
+        _ => respond(stream, "404 Not Found", &cors, &error_json("not found")),
+    }
+}
+
+/// Saved days and sessions between the optional `from` and `to` dates, both inclusive
+fn stats_json(query: &HashMap<String, String>) -> anyhow::Result<String> {
+    let from = export::parse_date(query.get("from").map(String::as_str))?;
+    let to = export::parse_date(query.get("to").map(String::as_str))?;
+    let days: Vec<DayStat> = storage::days()
+        .into_iter()
+        .filter(|x| from.map_or(true, |from| x.date >= from) && to.map_or(true, |to| x.date <= to))
+        .collect();
+    Ok(json!({
+        "from": from,
+        "to": to,
+        "work_second": days.iter().map(|x| x.work_second).sum::<u32>(),
+        "break_second": days.iter().map(|x| x.break_second).sum::<u32>(),
+        "days": days,
+        "sessions": storage::sessions(from, to),
+    })
+    .to_string())
+}
+
+/// Sends the status whenever it changes, until the client goes away
+fn events(mut stream: &TcpStream, cors: &str) -> anyhow::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n{cors}\r\n"
+    )?;
+    let mut seen = 0;
+    loop {
+        let version = {
+            let mut status = STATUS.lock().unwrap();
+            while status.0 == seen {
+                let (guard, wait) = CHANGED.wait_timeout(status, KEEPALIVE).unwrap();
+                status = guard;
+                if wait.timed_out() {
+                    break;
+                }
+            }
+            status.0
+        };
+        if version == seen {
+            write!(stream, ": keepalive\n\n")?;
+        } else {
+            seen = version;
+            write!(stream, "event: status\ndata: {}\n\n", status_json())?;
+        }
+        stream.flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Serves `request`, with `PORT` replaced by the server's, returns the response
+    /// and whether it ran a timer command
+    fn serve(request: &str) -> (String, bool) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        client
+            .write_all(request.replace("PORT", &port.to_string()).as_bytes())
+            .unwrap();
+        let (server, _) = listener.accept().unwrap();
+        let ran = AtomicBool::new(false);
+        handle(&server, port, &|_| ran.store(true, Ordering::SeqCst)).unwrap();
+        drop(server);
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        (response, ran.load(Ordering::SeqCst))
+    }
+
+    #[test]
+    fn local_request_is_accepted() {
+        let (response, ran) = serve("POST /toggle HTTP/1.1\r\nHost: 127.0.0.1:PORT\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 202"), "{response}");
+        assert!(ran);
+    }
+
+    #[test]
+    fn foreign_host_is_rejected() {
+        for host in [
+            "evil.example:PORT",
+            "127.0.0.1.evil.example:PORT",
+            "localhost:1",
+        ] {
+            let (response, ran) = serve(&format!("POST /toggle HTTP/1.1\r\nHost: {host}\r\n\r\n"));
+            assert!(response.starts_with("HTTP/1.1 403"), "{host}: {response}");
+            assert!(!ran, "{host}");
+        }
+        let (response, _) = serve("POST /toggle HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 403"), "{response}");
+    }
+
+    #[test]
+    fn foreign_origin_is_rejected() {
+        for origin in [
+            "https://evil.example",
+            "http://localhost.evil.example",
+            "null",
+        ] {
+            let (response, ran) = serve(&format!(
+                "POST /toggle HTTP/1.1\r\nHost: localhost:PORT\r\nOrigin: {origin}\r\n\r\n"
+            ));
+            assert!(response.starts_with("HTTP/1.1 403"), "{origin}: {response}");
+            assert!(!ran, "{origin}");
+        }
+    }
+
+    #[test]
+    fn trusted_origins() {
+        assert!(trusted("http://localhost:8080"));
+        assert!(trusted("http://127.0.0.1"));
+        assert!(trusted("http://[::1]:3000"));
+        assert!(trusted("moz-extension://0b2a9f1e"));
+        assert!(!trusted("http://localhost@evil.example"));
+        assert!(!trusted("file://"));
+    }
+}
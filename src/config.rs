@@ -0,0 +1,249 @@
+//! Versioning, migration and validation of the config file.
+use crate::{profile, shortcuts, state, storage, Config};
+use anyhow::{bail, Context};
+use toml::value::Table;
+
+/// Version written by this build, bump it together with a new entry in `MIGRATIONS`
+pub const VERSION: u32 = 3;
+
+/// Entry `n` upgrades a config from version `n` to `n + 1`
+const MIGRATIONS: &[fn(&mut Table)] = &[
+    // version 0 files predate versioning, the fields added since then fall back to defaults
+    |_| (),
+    // `storage` moved into the statistics file, `load` carries it over
+    |table| {
+        table.remove("storage");
+    },
+    // the timer shortcuts were bare keys, untouched ones get the new defaults with a modifier
+    |table| {
+        let shortcuts = match table.get_mut("shortcuts") {
+            Some(toml::Value::Table(x)) => x,
+            _ => return,
+        };
+        for (action, old, new) in [
+            ("toggle", "space", "<Control>space"),
+            ("break", "b", "<Control>b"),
+            ("reset", "r", "<Control>r"),
+        ] {
+            if shortcuts.get(action).and_then(toml::Value::as_str) == Some(old) {
+                shortcuts.insert(action.to_string(), toml::Value::String(new.to_string()));
+            }
+        }
+    },
+];
+
+/// Hands the backend of a config from before version 2 to the statistics file
+fn carry_storage(text: &str) -> anyhow::Result<()> {
+    let table: Table = toml::from_str(text)?;
+    if let Some(backend) = table.get("storage") {
+        storage::adopt(backend.clone().try_into()?)?;
+    }
+    Ok(())
+}
+
+/// Number fields where 0 turns the feature off, negative ones get a clearer error than serde's
+const DISABLED_BY_ZERO: [&str; 8] = [
+    "strict_break_seconds",
+    "postpone_minutes",
+    "min_focus_minutes",
+    "max_flow_minutes",
+    "idle_minutes",
+    "daily_goal_minutes",
+    "weekly_goal_minutes",
+    "api_port",
+];
+
+/// Parses a config, migrating it to the current version, returns whether it was migrated
+pub fn parse(text: &str) -> anyhow::Result<(Config, bool)> {
+    let mut table: Table = toml::from_str(text)?;
+    let version = match table.get("version") {
+        None => 0,
+        Some(toml::Value::Integer(x)) if *x >= 0 => *x as u32,
+        Some(_) => bail!("version has to be a non-negative integer"),
+    };
+    for key in DISABLED_BY_ZERO {
+        if matches!(table.get(key), Some(toml::Value::Integer(x)) if *x < 0) {
+            bail!("{key} has to be a non-negative integer (0 disables)");
+        }
+    }
+    if version > VERSION {
+        bail!("config version {version} is newer than the supported version {VERSION}");
+    }
+    for migration in MIGRATIONS.iter().skip(version as usize) {
+        migration(&mut table);
+    }
+    table.insert("version".to_string(), toml::Value::Integer(VERSION as i64));
+    let config: Config = toml::Value::Table(table).try_into()?;
+    Ok((config, version != VERSION))
+}
+
+/// Readable descriptions of everything wrong with the config
+pub fn validate(config: &Config) -> Vec<String> {
+    let mut errors = vec![];
+    if config.break_ratio == 0 {
+        errors.push("break_ratio has to be at least 1".to_string());
+    }
+    if !config.sound.as_os_str().is_empty() && !config.sound.exists() {
+        errors.push(format!(
+            "sound file {} does not exist",
+            config.sound.display()
+        ));
+    }
+    if config.min_focus_minutes != 0
+        && config.max_flow_minutes != 0
+        && config.min_focus_minutes >= config.max_flow_minutes
+    {
+        errors.push("min_focus_minutes has to be below max_flow_minutes".to_string());
+    }
+    if config.postpone_minutes > 60 {
+        errors.push("postpone_minutes can't be longer than an hour".to_string());
+    }
+    if config.idle_minutes > 120 {
+        errors.push("idle_minutes can't be longer than two hours".to_string());
+    }
+    if config.daily_goal_minutes > 24 * 60 {
+        errors.push("daily_goal_minutes can't be longer than a day".to_string());
+    }
+    if config.weekly_goal_minutes > 7 * 24 * 60 {
+        errors.push("weekly_goal_minutes can't be longer than a week".to_string());
+    }
+    for (weekday, minutes) in config.weekday_goal_minutes.iter() {
+        if *weekday > 6 {
+            errors.push(format!(
+                "weekday_goal_minutes has weekday {weekday}, expected 0 (monday) to 6 (sunday)"
+            ));
+        }
+        if *minutes > 24 * 60 {
+            errors.push(format!(
+                "weekday_goal_minutes for weekday {weekday} can't be longer than a day"
+            ));
+        }
+    }
+    for url in config.webhooks.iter() {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            errors.push(format!(
+                "webhook {url:?} has to be an http:// or https:// URL"
+            ));
+        }
+    }
+    errors.extend(shortcuts::validate(&config.shortcuts));
+    errors
+}
+
+/// Loads, migrates and validates a config file, creating it if it doesn't exist yet
+pub fn load(name: &str) -> anyhow::Result<Config> {
+    let path = state::path(name);
+    if !path.exists() {
+        let config = Config::default();
+        confy::store("flowtime", Some(name), &config)?;
+        return Ok(config);
+    }
+    let text = std::fs::read_to_string(&path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let (config, migrated) =
+        parse(&text).with_context(|| format!("invalid config {}", path.display()))?;
+    let errors = validate(&config);
+    if !errors.is_empty() {
+        bail!("invalid config {}:\n{}", path.display(), errors.join("\n"));
+    }
+    if migrated {
+        carry_storage(&text)?;
+        confy::store("flowtime", Some(name), &config)?;
+    }
+    Ok(config)
+}
+
+/// `flowtime config check [PROFILE]`, checking the active profile by default
+pub fn command(args: &[String]) -> anyhow::Result<()> {
+    match args.first().map(String::as_str) {
+        Some("check") => {
+            let profile = args.get(1).cloned().unwrap_or_else(profile::active);
+            let path = state::path(&profile::config_name(&profile));
+            let text = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let (config, migrated) = parse(&text)?;
+            let errors = validate(&config);
+            for error in errors.iter() {
+                println!("error: {}", error);
+            }
+            if migrated {
+                println!("{} will be migrated to version {}", path.display(), VERSION);
+            }
+            if !errors.is_empty() {
+                bail!("{} has {} errors", path.display(), errors.len());
+            }
+            println!("{} is valid", path.display());
+            Ok(())
+        }
+        _ => bail!("usage: flowtime config check [PROFILE]"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_1_migrates_to_current() {
+        let text = r#"
+            version = 1
+            break_ratio = 4
+            storage = "sqlite"
+
+            [shortcuts]
+            toggle = "space"
+            break = "b"
+            reset = "<Alt>r"
+        "#;
+        let (config, migrated) = parse(text).unwrap();
+        assert!(migrated);
+        assert_eq!(config.version, VERSION);
+        assert_eq!(config.break_ratio, 4);
+        assert_eq!(config.shortcuts.toggle, "<Control>space");
+        assert_eq!(config.shortcuts.take_break, "<Control>b");
+        // changed by the user, so it stays
+        assert_eq!(config.shortcuts.reset, "<Alt>r");
+    }
+
+    #[test]
+    fn current_version_is_not_migrated() {
+        let (config, migrated) = parse(&format!("version = {VERSION}\nbreak_ratio = 3")).unwrap();
+        assert!(!migrated);
+        assert_eq!(config.break_ratio, 3);
+    }
+
+    #[test]
+    fn unusable_versions_are_refused() {
+        assert!(parse(&format!("version = {}", VERSION + 1)).is_err());
+        assert!(parse("version = -1").is_err());
+        assert!(parse("version = \"3\"").is_err());
+    }
+
+    #[test]
+    fn negative_numbers_say_what_0_does() {
+        let error = parse("idle_minutes = -5").unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "idle_minutes has to be a non-negative integer (0 disables)"
+        );
+    }
+
+    #[test]
+    fn validate_reports_each_problem() {
+        assert!(validate(&Config::default()).is_empty());
+        let config = Config {
+            break_ratio: 0,
+            min_focus_minutes: 30,
+            max_flow_minutes: 30,
+            idle_minutes: 121,
+            ..Config::default()
+        };
+        assert_eq!(validate(&config).len(), 3);
+        let config = Config {
+            min_focus_minutes: 0,
+            max_flow_minutes: 0,
+            ..Config::default()
+        };
+        assert!(validate(&config).is_empty());
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{storage, Session};
+use anyhow::{anyhow, bail, Context};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Format {
+    Csv,
+    Json,
+    Ics,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "ics" | "ical" | "icalendar" => Ok(Format::Ics),
+            _ => Err(anyhow!(
+                "unknown export format {s:?}, expected csv, json or ics"
+            )),
+        }
+    }
+}
+
+impl Format {
+    /// Guesses the format from a file extension, falling back to csv
+    pub fn from_path(path: &Path) -> Format {
+        path.extension()
+            .and_then(|x| x.to_str())
+            .and_then(|x| x.parse().ok())
+            .unwrap_or(Format::Csv)
+    }
+}
+
+/// Sessions that started between `from` and `to`, both inclusive
+pub fn sessions_between(
+    sessions: &[Session],
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Vec<Session> {
+    sessions
+        .iter()
+        .filter(|session| {
+            let date = session.start.date_naive();
+            from.map_or(true, |from| date >= from) && to.map_or(true, |to| date <= to)
+        })
+        .cloned()
+        .collect()
+}
+
+pub fn export(sessions: &[Session], format: Format) -> anyhow::Result<String> {
+    match format {
+        Format::Csv => to_csv(sessions),
+        Format::Json => Ok(serde_json::to_string_pretty(sessions)?),
+        Format::Ics => Ok(to_ics(sessions)),
+    }
+}
+
+fn to_csv(sessions: &[Session]) -> anyhow::Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record([
+        "start",
+        "end",
+        "work_second",
+        "break_second",
+        "tag",
+        "profile",
+    ])?;
+    for session in sessions {
+        writer.write_record([
+            session.start.to_rfc3339(),
+            session.end.to_rfc3339(),
+            session.work_second.to_string(),
+            session.break_second.to_string(),
+            session.tag.clone(),
+            session.profile.clone(),
+        ])?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+fn ics_time(time: &DateTime<Local>) -> String {
+    time.with_timezone(&Utc)
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+fn ics_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Folds a content line into lines of at most 75 octets, each continuation starting with
+/// a space, without splitting a UTF-8 character
+fn ics_fold(line: &str) -> String {
+    let mut output = String::with_capacity(line.len());
+    // the leading space of a continuation counts towards its 75 octets
+    let mut room = 75;
+    for c in line.chars() {
+        if c.len_utf8() > room {
+            output.push_str("\r\n ");
+            room = 74;
+        }
+        output.push(c);
+        room -= c.len_utf8();
+    }
+    output
+}
+
+/// One VEVENT per work session, lines are folded and CRLF terminated as RFC 5545 requires
+fn to_ics(sessions: &[Session]) -> String {
+    let now = ics_time(&Local::now());
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Flowtime//Flowtime//EN".to_string(),
+    ];
+    for session in sessions {
+        let summary = if session.tag.is_empty() {
+            "Flowtime".to_string()
+        } else {
+            format!("Flowtime: {}", session.tag)
+        };
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}@flowtime", session.start.timestamp()),
+            format!("DTSTAMP:{}", now),
+            format!("DTSTART:{}", ics_time(&session.start)),
+            format!("DTEND:{}", ics_time(&session.end)),
+            format!("SUMMARY:{}", ics_text(&summary)),
+            format!(
+                "DESCRIPTION:{}",
+                ics_text(&format!(
+                    "Worked {} seconds, earned {} seconds of break, profile {}",
+                    session.work_second, session.break_second, session.profile
+                ))
+            ),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| ics_fold(line) + "\r\n").collect()
+}
+
+/// Value following a `--name` flag
+pub fn flag<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|x| x == name)
+        .and_then(|x| args.get(x + 1))
+        .map(String::as_str)
+}
+
+pub fn parse_date(date: Option<&str>) -> anyhow::Result<Option<NaiveDate>> {
+    date.map(|x| {
+        NaiveDate::parse_from_str(x, "%Y-%m-%d")
+            .with_context(|| format!("invalid date {x:?}, expected YYYY-MM-DD"))
+    })
+    .transpose()
+}
+
+/// `flowtime export --format csv|json|ics [--from YYYY-MM-DD] [--to YYYY-MM-DD] [--output FILE]`
+pub fn command(args: &[String]) -> anyhow::Result<()> {
+    let output = flag(args, "--output");
+    let format = match (flag(args, "--format"), output) {
+        (Some(format), _) => format.parse()?,
+        (None, Some(output)) => Format::from_path(Path::new(output)),
+        (None, None) => bail!("missing --format csv|json|ics"),
+    };
+    let sessions = storage::sessions(
+        parse_date(flag(args, "--from"))?,
+        parse_date(flag(args, "--to"))?,
+    );
+    let exported = export(&sessions, format)?;
+    match output {
+        Some(output) => {
+            std::fs::write(output, exported).with_context(|| format!("failed to write {output}"))?
+        }
+        None => print!("{}", exported),
+    }
+    Ok(())
+}
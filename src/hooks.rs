@@ -0,0 +1,135 @@
+//! User commands run on timer transitions, configured in the `[hooks]` table of the config.
+//!
+//! Commands run through `sh -c` with the state in `FLOWTIME_*` environment variables and as
+//! JSON on their stdin. Nothing waits for them, failures are only printed.
+use crate::timer::{mode_key, TimerMode};
+use crate::{cfg, day_work, today};
+use chrono::{DateTime, Local};
+use serde_derive::{Deserialize, Serialize};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// A shell command per event, empty ones are skipped
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Hooks {
+    pub work_started: String,
+    pub work_paused: String,
+    pub break_started: String,
+    pub break_ended: String,
+    pub session_reset: String,
+    pub goal_reached: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    WorkStarted,
+    WorkPaused,
+    BreakStarted,
+    BreakEnded,
+    SessionReset,
+    GoalReached,
+}
+
+impl Event {
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::WorkStarted => "work_started",
+            Event::WorkPaused => "work_paused",
+            Event::BreakStarted => "break_started",
+            Event::BreakEnded => "break_ended",
+            Event::SessionReset => "session_reset",
+            Event::GoalReached => "goal_reached",
+        }
+    }
+
+    fn command(self, hooks: &Hooks) -> &str {
+        match self {
+            Event::WorkStarted => &hooks.work_started,
+            Event::WorkPaused => &hooks.work_paused,
+            Event::BreakStarted => &hooks.break_started,
+            Event::BreakEnded => &hooks.break_ended,
+            Event::SessionReset => &hooks.session_reset,
+            Event::GoalReached => &hooks.goal_reached,
+        }
+    }
+}
+
+/// What hooks and webhooks get to know about the timer
+#[derive(Debug, Clone, Serialize)]
+pub struct Payload {
+    pub event: Event,
+    /// `work`, `break`, `paused` or `stopped`
+    pub mode: &'static str,
+    /// Worked so far in a session, or left of a break
+    pub seconds: u32,
+    /// Work saved for today, without the running session
+    pub today_work_second: u32,
+    pub tag: String,
+    /// `daily` or `weekly` for `goal_reached`
+    pub goal: Option<&'static str>,
+    pub time: DateTime<Local>,
+}
+
+impl Payload {
+    pub fn new(event: Event, mode: &TimerMode, seconds: u32, tag: &str) -> Payload {
+        Payload {
+            event,
+            mode: mode_key(mode),
+            seconds,
+            today_work_second: day_work(today()),
+            tag: tag.to_string(),
+            goal: None,
+            time: Local::now(),
+        }
+    }
+
+    fn env(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("FLOWTIME_EVENT", self.event.name().to_string()),
+            ("FLOWTIME_MODE", self.mode.to_string()),
+            ("FLOWTIME_SECONDS", self.seconds.to_string()),
+            ("FLOWTIME_TODAY_WORK", self.today_work_second.to_string()),
+            ("FLOWTIME_TAG", self.tag.clone()),
+            ("FLOWTIME_GOAL", self.goal.unwrap_or_default().to_string()),
+            ("FLOWTIME_TIME", self.time.to_rfc3339()),
+        ]
+    }
+}
+
+/// Runs the hook of `payload.event` in the background, if one is configured
+pub fn run(payload: &Payload) {
+    let command = payload.event.command(&cfg!().hooks).to_string();
+    if command.trim().is_empty() {
+        return;
+    }
+    let json = serde_json::to_string(payload).unwrap();
+    let env = payload.env();
+    std::thread::spawn(move || {
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .envs(env)
+            .stdin(Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(x) => x,
+            Err(e) => {
+                eprintln!("flowtime: failed to run hook {:?}: {}", command, e);
+                return;
+            }
+        };
+        // hooks that don't read their stdin close it early, which is fine
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(json.as_bytes()).ok();
+        }
+        match child.wait() {
+            Ok(status) if !status.success() => {
+                eprintln!("flowtime: hook {:?} exited with {}", command, status)
+            }
+            Err(e) => eprintln!("flowtime: failed to wait for hook {:?}: {}", command, e),
+            _ => (),
+        }
+    });
+}
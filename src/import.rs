@@ -0,0 +1,392 @@
+use crate::export::flag;
+use crate::{hour_minute, storage, Session, Stats, CURRENT_MONTH};
+use anyhow::{anyhow, bail, Context};
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc,
+};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Source {
+    Toggl,
+    Clockify,
+    Pomodoro,
+    Timewarrior,
+    Flowtime,
+}
+
+impl FromStr for Source {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "toggl" => Ok(Source::Toggl),
+            "clockify" => Ok(Source::Clockify),
+            "pomodoro" => Ok(Source::Pomodoro),
+            "timewarrior" | "timew" => Ok(Source::Timewarrior),
+            "flowtime" | "json" => Ok(Source::Flowtime),
+            _ => Err(anyhow!(
+                "unknown import format {s:?}, expected toggl, clockify, pomodoro, timewarrior or flowtime"
+            )),
+        }
+    }
+}
+
+/// Parses an export of `source`, `date_format` is the strftime format of its dates, which
+/// is guessed when unambiguous otherwise
+pub fn parse(
+    source: Source,
+    content: &str,
+    date_format: Option<&str>,
+) -> anyhow::Result<Vec<Session>> {
+    match source {
+        // both export the same "Start date, Start time, End date, End time" columns
+        Source::Toggl | Source::Clockify => parse_tracker_csv(content, date_format),
+        Source::Pomodoro => parse_pomodoro_csv(content),
+        Source::Timewarrior => parse_timewarrior(content),
+        Source::Flowtime => Ok(serde_json::from_str(content)?),
+    }
+}
+
+/// Parses `date` with `format`, or else as ISO, dotted day first or slashed dates. Slashed
+/// dates are only taken when one of the numbers is above 12, 03/04/2024 could be either.
+fn parse_date(date: &str, format: Option<&str>) -> anyhow::Result<NaiveDate> {
+    let date = date.trim();
+    if let Some(format) = format {
+        return NaiveDate::parse_from_str(date, format)
+            .with_context(|| format!("date {date:?} doesn't match {format:?}"));
+    }
+    if let Some(x) = ["%Y-%m-%d", "%d.%m.%Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(date, format).ok())
+    {
+        return Ok(x);
+    }
+    let month_first = NaiveDate::parse_from_str(date, "%m/%d/%Y").ok();
+    let day_first = NaiveDate::parse_from_str(date, "%d/%m/%Y").ok();
+    match (month_first, day_first) {
+        (Some(x), Some(y)) if x != y => bail!(
+            "date {date:?} could be month or day first, pass --date-format %m/%d/%Y or %d/%m/%Y"
+        ),
+        (Some(x), _) | (_, Some(x)) => Ok(x),
+        (None, None) => bail!("unrecognized date {date:?}"),
+    }
+}
+
+fn parse_time(time: &str) -> Option<NaiveTime> {
+    ["%H:%M:%S", "%I:%M:%S %p", "%H:%M", "%I:%M %p"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(time.trim(), format).ok())
+}
+
+/// Parses RFC 3339 timestamps or local "YYYY-MM-DD HH:MM[:SS]" ones
+fn parse_datetime(datetime: &str) -> Option<DateTime<Local>> {
+    let datetime = datetime.trim();
+    if let Ok(x) = DateTime::parse_from_rfc3339(datetime) {
+        return Some(x.with_timezone(&Local));
+    }
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(datetime, format).ok())
+        .and_then(local)
+}
+
+fn local(datetime: NaiveDateTime) -> Option<DateTime<Local>> {
+    Local.from_local_datetime(&datetime).earliest()
+}
+
+fn session(start: DateTime<Local>, end: DateTime<Local>, tag: String) -> Option<Session> {
+    let work_second = (end - start).num_seconds();
+    (work_second > 0).then(|| Session {
+        start,
+        end,
+        work_second: work_second as u32,
+        break_second: 0,
+        tag,
+        profile: String::new(),
+    })
+}
+
+/// Column index by case insensitive header name, trying each name in order
+fn column(headers: &csv::StringRecord, names: &[&str]) -> Option<usize> {
+    names.iter().find_map(|name| {
+        headers
+            .iter()
+            .position(|header| header.trim().eq_ignore_ascii_case(name))
+    })
+}
+
+fn tag_of(record: &csv::StringRecord, columns: &[Option<usize>]) -> String {
+    columns
+        .iter()
+        .flatten()
+        .filter_map(|x| record.get(*x))
+        .find(|x| !x.trim().is_empty())
+        .unwrap_or("")
+        .trim()
+        .to_string()
+}
+
+fn parse_tracker_csv(content: &str, date_format: Option<&str>) -> anyhow::Result<Vec<Session>> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let headers = reader.headers()?.clone();
+    let required =
+        |name: &str| column(&headers, &[name]).ok_or_else(|| anyhow!("missing {name:?} column"));
+    let (start_date, start_time) = (required("start date")?, required("start time")?);
+    let (end_date, end_time) = (required("end date")?, required("end time")?);
+    let tags = [
+        column(&headers, &["description"]),
+        column(&headers, &["project"]),
+    ];
+
+    let mut sessions = vec![];
+    for (line, record) in reader.records().enumerate() {
+        let record = record?;
+        let row = line + 2;
+        let datetime = |date: usize, time: usize| -> anyhow::Result<DateTime<Local>> {
+            let date = parse_date(record.get(date).unwrap_or_default(), date_format)
+                .with_context(|| format!("invalid date on row {row}"))?;
+            record
+                .get(time)
+                .and_then(parse_time)
+                .and_then(|time| local(date.and_time(time)))
+                .ok_or_else(|| anyhow!("invalid time on row {row}"))
+        };
+        let (start, end) = (
+            datetime(start_date, start_time)?,
+            datetime(end_date, end_time)?,
+        );
+        sessions.extend(session(start, end, tag_of(&record, &tags)));
+    }
+    Ok(sessions)
+}
+
+/// Pomodoro apps export a start timestamp with either an end timestamp or a duration in minutes
+fn parse_pomodoro_csv(content: &str) -> anyhow::Result<Vec<Session>> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let headers = reader.headers()?.clone();
+    let start = column(&headers, &["start", "started at", "start time", "started"])
+        .ok_or_else(|| anyhow!("missing start column"))?;
+    let end = column(
+        &headers,
+        &["end", "ended at", "end time", "ended", "finished at"],
+    );
+    let duration = column(&headers, &["duration", "minutes", "duration (minutes)"]);
+    if end.is_none() && duration.is_none() {
+        bail!("missing end or duration column");
+    }
+    let tags = [
+        column(&headers, &["task"]),
+        column(&headers, &["name"]),
+        column(&headers, &["title"]),
+        column(&headers, &["description"]),
+        column(&headers, &["project"]),
+    ];
+
+    let mut sessions = vec![];
+    for (line, record) in reader.records().enumerate() {
+        let record = record?;
+        let invalid = || anyhow!("invalid start, end or duration on row {}", line + 2);
+        let start = record
+            .get(start)
+            .and_then(parse_datetime)
+            .ok_or_else(invalid)?;
+        let end = match (end.and_then(|x| record.get(x)), duration) {
+            (Some(end), _) if !end.trim().is_empty() => parse_datetime(end),
+            (_, Some(duration)) => record
+                .get(duration)
+                .and_then(|x| x.trim().parse::<f64>().ok())
+                .map(|minutes| start + Duration::seconds((minutes * 60.0) as i64)),
+            _ => None,
+        }
+        .ok_or_else(invalid)?;
+        sessions.extend(session(start, end, tag_of(&record, &tags)));
+    }
+    Ok(sessions)
+}
+
+/// Timewarrior data files have lines like `inc 20230301T090000Z - 20230301T103000Z # tag "other tag"`
+fn parse_timewarrior(content: &str) -> anyhow::Result<Vec<Session>> {
+    let utc = |x: &str| {
+        NaiveDateTime::parse_from_str(x, "%Y%m%dT%H%M%SZ")
+            .map(|x| Utc.from_utc_datetime(&x).with_timezone(&Local))
+    };
+    let mut sessions = vec![];
+    for (line, text) in content.lines().enumerate() {
+        let (interval, tags) = match text.split_once('#') {
+            Some((interval, tags)) => (interval, tags.trim()),
+            None => (text, ""),
+        };
+        let words: Vec<&str> = interval.split_whitespace().collect();
+        match words.as_slice() {
+            ["inc", start, "-", end] => {
+                let invalid = || format!("invalid interval on line {}", line + 1);
+                let start = utc(start).with_context(invalid)?;
+                let end = utc(end).with_context(invalid)?;
+                sessions.extend(session(start, end, tags.replace('"', "")));
+            }
+            // intervals that are still open have no end yet
+            ["inc", _] | [] => (),
+            _ => bail!("unrecognized line {}", line + 1),
+        }
+    }
+    Ok(sessions)
+}
+
+#[derive(Default, Debug)]
+pub struct Summary {
+    pub read: usize,
+    pub duplicates: usize,
+    pub imported: Vec<Session>,
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} sessions read", self.read)?;
+        writeln!(f, "{} duplicates skipped", self.duplicates)?;
+        write!(f, "{} new sessions", self.imported.len())?;
+        if let (Some(first), Some(last)) = (
+            self.imported.iter().map(|x| x.start).min(),
+            self.imported.iter().map(|x| x.end).max(),
+        ) {
+            write!(
+                f,
+                " from {} to {}, {} of work",
+                first.date_naive(),
+                last.date_naive(),
+                hour_minute(self.imported.iter().map(|x| x.work_second).sum())
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Drops sessions whose start matches an already recorded one, down to the second
+pub fn deduplicate(existing: &[Session], sessions: Vec<Session>) -> Summary {
+    let mut seen: HashSet<i64> = existing.iter().map(|x| x.start.timestamp()).collect();
+    let mut summary = Summary {
+        read: sessions.len(),
+        ..Default::default()
+    };
+    for session in sessions {
+        if seen.insert(session.start.timestamp()) {
+            summary.imported.push(session);
+        } else {
+            summary.duplicates += 1;
+        }
+    }
+    summary
+}
+
+/// Adds sessions to the month totals, which have no year so only the running month is kept
+pub fn apply_months(stats: &mut Stats, sessions: &[Session]) {
+    let now = Local::now();
+    for session in sessions {
+        let date = session.start.date_naive();
+        if date.year() == now.year() && date.month() == *CURRENT_MONTH {
+            match stats
+                .month_break_work
+                .iter_mut()
+                .find(|(month, _, _)| month == &*CURRENT_MONTH)
+            {
+                Some((_, break_second, work_second)) => {
+                    *break_second += session.break_second;
+                    *work_second += session.work_second;
+                }
+                None => stats.month_break_work.push((
+                    *CURRENT_MONTH,
+                    session.break_second,
+                    session.work_second,
+                )),
+            }
+        }
+    }
+}
+
+/// `flowtime import --format toggl|clockify|pomodoro|timewarrior|flowtime --input FILE
+/// [--date-format FORMAT] [--dry-run]`
+pub fn command(args: &[String]) -> anyhow::Result<()> {
+    let source: Source = flag(args, "--format")
+        .ok_or_else(|| anyhow!("missing --format"))?
+        .parse()?;
+    let input = flag(args, "--input").ok_or_else(|| anyhow!("missing --input FILE"))?;
+    let content =
+        std::fs::read_to_string(input).with_context(|| format!("failed to read {input}"))?;
+    let sessions = parse(source, &content, flag(args, "--date-format"))
+        .with_context(|| format!("failed to parse {input}"))?;
+
+    let summary = deduplicate(&storage::sessions(None, None), sessions);
+    println!("{}", summary);
+    if args.iter().any(|x| x == "--dry-run") {
+        println!("dry run, nothing was saved");
+    } else {
+        storage::import(&summary.imported)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 3, 4, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn ambiguous_slashed_date_is_refused() {
+        assert!(parse_date("03/04/2024", None).is_err());
+        assert_eq!(
+            parse_date("03/04/2024", Some("%m/%d/%Y")).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 3, 4).unwrap()
+        );
+        assert_eq!(
+            parse_date("03/04/2024", Some("%d/%m/%Y")).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 4, 3).unwrap()
+        );
+    }
+
+    #[test]
+    fn unambiguous_dates_are_guessed() {
+        let date = NaiveDate::from_ymd_opt(2024, 4, 13).unwrap();
+        assert_eq!(parse_date("13/04/2024", None).unwrap(), date);
+        assert_eq!(parse_date("04/13/2024", None).unwrap(), date);
+        assert_eq!(parse_date("2024-04-13", None).unwrap(), date);
+        assert_eq!(parse_date("13.04.2024", None).unwrap(), date);
+        // the same day either way round
+        assert_eq!(
+            parse_date("04/04/2024", None).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 4, 4).unwrap()
+        );
+        assert!(parse_date("2024/13/45", None).is_err());
+    }
+
+    #[test]
+    fn tracker_csv_reports_the_ambiguous_row() {
+        let csv = "Start date,Start time,End date,End time\n\
+                   03/04/2024,09:00:00,03/04/2024,10:00:00\n";
+        let error = format!("{:#}", parse_tracker_csv(csv, None).unwrap_err());
+        assert!(error.contains("row 2"), "{error}");
+        assert!(error.contains("--date-format"), "{error}");
+        assert_eq!(parse_tracker_csv(csv, Some("%m/%d/%Y")).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn duplicates_are_matched_by_start() {
+        let existing: Vec<Session> = session(at(9), at(10), String::new()).into_iter().collect();
+        let sessions = [
+            session(at(9), at(11), "other end".to_string()),
+            session(at(12), at(13), String::new()),
+            session(at(12), at(13), String::new()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let summary = deduplicate(&existing, sessions);
+        assert_eq!(summary.read, 3);
+        assert_eq!(summary.duplicates, 2);
+        assert_eq!(summary.imported.len(), 1);
+        assert_eq!(summary.imported[0].start, at(12));
+    }
+}
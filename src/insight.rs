@@ -0,0 +1,109 @@
+use crate::{hour_minute, Session};
+use chrono::{DateTime, Datelike, Duration, Local, Timelike};
+
+/// Hours covered by a best focus window suggestion
+pub const WINDOW_HOURS: usize = 2;
+
+/// Spreads the work of a session evenly over the hours between its start and end
+fn spread(session: &Session, mut f: impl FnMut(DateTime<Local>, u32)) {
+    let span = (session.end - session.start).num_seconds();
+    if span <= 0 {
+        f(session.start, session.work_second);
+        return;
+    }
+    let mut cursor = session.start;
+    while cursor < session.end {
+        let next_hour = cursor
+            .with_minute(0)
+            .and_then(|x| x.with_second(0))
+            .and_then(|x| x.with_nanosecond(0))
+            .unwrap_or(cursor)
+            + Duration::hours(1);
+        let slot_end = next_hour.min(session.end);
+        let share =
+            (slot_end - cursor).num_seconds() as u64 * session.work_second as u64 / span as u64;
+        f(cursor, share as u32);
+        cursor = slot_end;
+    }
+}
+
+/// Work seconds for each hour of the day
+pub fn hourly_work(sessions: &[Session]) -> [u32; 24] {
+    let mut output = [0; 24];
+    for session in sessions {
+        spread(session, |time, second| {
+            output[time.hour() as usize] += second
+        });
+    }
+    output
+}
+
+/// Work seconds for each weekday starting from monday
+pub fn weekday_work(sessions: &[Session]) -> [u32; 7] {
+    let mut output = [0; 7];
+    for session in sessions {
+        spread(session, |time, second| {
+            output[time.weekday().num_days_from_monday() as usize] += second
+        });
+    }
+    output
+}
+
+/// Average session length grouped by the hour the session started in
+pub fn average_session_by_hour(sessions: &[Session]) -> [Option<u32>; 24] {
+    let mut total = [0u64; 24];
+    let mut count = [0u64; 24];
+    for session in sessions {
+        let hour = session.start.hour() as usize;
+        total[hour] += session.work_second as u64;
+        count[hour] += 1;
+    }
+    std::array::from_fn(|hour| (count[hour] != 0).then(|| (total[hour] / count[hour]) as u32))
+}
+
+/// Start hour of the `WINDOW_HOURS` long window with the most work, wrapping around midnight
+pub fn best_window(hourly: &[u32; 24]) -> Option<usize> {
+    (0..24)
+        .map(|start| {
+            let work: u32 = (0..WINDOW_HOURS).map(|x| hourly[(start + x) % 24]).sum();
+            (start, work)
+        })
+        .filter(|(_, work)| *work != 0)
+        .max_by_key(|(_, work)| *work)
+        .map(|(start, _)| start)
+}
+
+/// Describes the best focus window, e.g. "09:00 - 11:00"
+pub fn window_string(start: usize) -> String {
+    format!("{:02}:00 - {:02}:00", start, (start + WINDOW_HOURS) % 24)
+}
+
+/// Plain text report used by the `insights` command
+pub fn report(sessions: &[Session]) -> String {
+    let hourly = hourly_work(sessions);
+    let average = average_session_by_hour(sessions);
+    let max = hourly.iter().max().copied().unwrap_or(0).max(1);
+    let mut output = String::from("hour  work      avg session\n");
+    for (hour, work) in hourly.iter().enumerate() {
+        output.push_str(&format!(
+            "{:02}    {:<9} {:<11} {}\n",
+            hour,
+            hour_minute(*work),
+            average[hour]
+                .map(hour_minute)
+                .unwrap_or_else(|| "-".to_string()),
+            "#".repeat((*work as u64 * 30 / max as u64) as usize)
+        ));
+    }
+    output.push('\n');
+    let weekdays = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+    for (weekday, work) in weekdays.iter().zip(weekday_work(sessions).iter()) {
+        output.push_str(&format!("{}   {}\n", weekday, hour_minute(*work)));
+    }
+    output.push('\n');
+    match best_window(&hourly) {
+        Some(start) => output.push_str(&format!("Best focus window: {}\n", window_string(start))),
+        None => output.push_str("Not enough sessions recorded yet\n"),
+    }
+    output
+}
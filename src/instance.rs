@@ -0,0 +1,132 @@
+//! Keeps Flowtime to a single instance, later launches hand their command to the running one.
+//!
+//! Uniqueness comes from GApplication: the first process owns `APP_ID` on the session bus,
+//! the commands are its `app.*` actions and a plain second launch activates it again.
+use anyhow::{bail, Result};
+use gtk::gio;
+use gtk::glib::{self, ToVariant, VariantTy};
+use gtk::prelude::*;
+
+pub const APP_ID: &str = "io.github.JustSimplyKyle.Flowtime";
+
+/// Command line flags, each activating the app action of the same name
+pub const ACTIONS: [&str; 6] = ["toggle", "break", "reset", "interruption", "show", "quit"];
+
+/// App action switching to the profile named by its string parameter, from `--profile NAME`
+pub const PROFILE: &str = "profile";
+
+/// Stateful app action holding the unsaved work of the running session, for `flowtime status`
+pub const SESSION_WORK: &str = "session-work";
+
+/// An app action with its parameter
+pub struct Command {
+    action: &'static str,
+    target: Option<glib::Variant>,
+}
+
+/// The action of a `--flag` argument
+pub fn action(arg: &str) -> Result<&'static str> {
+    match arg
+        .strip_prefix("--")
+        .and_then(|x| ACTIONS.iter().find(|action| **action == x))
+    {
+        Some(action) => Ok(action),
+        None => bail!(
+            "unknown argument {arg:?}, expected a command, --{PROFILE} NAME or one of {}",
+            ACTIONS.map(|x| format!("--{x}")).join(" ")
+        ),
+    }
+}
+
+/// The command of the arguments after the program name, if there is one
+pub fn command(args: &[String]) -> Result<Option<Command>> {
+    match args {
+        [] => Ok(None),
+        [flag, name] if flag == "--profile" => Ok(Some(Command {
+            action: PROFILE,
+            target: Some(name.to_variant()),
+        })),
+        [flag] if flag == "--profile" => bail!("usage: flowtime --profile NAME"),
+        [arg] => Ok(Some(Command {
+            action: action(arg)?,
+            target: None,
+        })),
+        [_, arg, ..] => bail!("unexpected argument {arg:?}"),
+    }
+}
+
+/// Registers the app and hands `command` to the running instance if there is one.
+///
+/// Returns whether there is nothing left to do, otherwise this process is the primary
+/// instance and `command` runs as soon as the window is up.
+pub fn forward(command: Option<Command>) -> Result<bool> {
+    let app = relm4::main_application();
+    app.register(None::<&gio::Cancellable>)?;
+    if !app.is_remote() {
+        return Ok(match command {
+            // nothing to quit
+            Some(Command { action: "quit", .. }) => true,
+            Some(command) => {
+                glib::idle_add_local_once(move || {
+                    relm4::main_application()
+                        .activate_action(command.action, command.target.as_ref())
+                });
+                false
+            }
+            None => false,
+        });
+    }
+    match command {
+        Some(command) => app.activate_action(command.action, command.target.as_ref()),
+        None => app.activate(),
+    }
+    // the calls are only queued, exiting right away would drop them
+    gio::bus_get_sync(gio::BusType::Session, None::<&gio::Cancellable>)?
+        .flush_sync(None::<&gio::Cancellable>)?;
+    Ok(true)
+}
+
+/// Adds the `SESSION_WORK` action to the primary instance, remote changes to it are ignored
+pub fn add_session_work() {
+    let action = gio::SimpleAction::new_stateful(SESSION_WORK, None, &0u32.to_variant());
+    action.connect_change_state(|_, _| ());
+    relm4::main_application().add_action(&action);
+}
+
+/// Updates the state of `SESSION_WORK`, which is sent to whoever watches the actions
+pub fn set_session_work(second: u32) {
+    if let Some(action) = relm4::main_application()
+        .lookup_action(SESSION_WORK)
+        .and_then(|x| x.downcast::<gio::SimpleAction>().ok())
+    {
+        if action.state().and_then(|x| x.get::<u32>()) != Some(second) {
+            action.set_state(&second.to_variant());
+        }
+    }
+}
+
+/// Unsaved work of the session in the running instance, `None` when no instance runs
+pub fn session_work() -> Option<u32> {
+    let connection = gio::bus_get_sync(gio::BusType::Session, None::<&gio::Cancellable>).ok()?;
+    // the object path GApplication exports its actions on
+    let path = format!("/{}", APP_ID.replace('.', "/"));
+    let reply = connection
+        .call_sync(
+            Some(APP_ID),
+            &path,
+            "org.gtk.Actions",
+            "Describe",
+            Some(&(SESSION_WORK,).to_variant()),
+            Some(VariantTy::new("(bgav)").unwrap()),
+            gio::DBusCallFlags::NO_AUTO_START,
+            1000,
+            None::<&gio::Cancellable>,
+        )
+        .ok()?;
+    // enabled, parameter type and the state, if any, in an array
+    let state = reply.child_value(2);
+    if state.n_children() == 0 {
+        return None;
+    }
+    state.child_value(0).as_variant()?.get::<u32>()
+}
@@ -1,23 +1,73 @@
+pub mod api;
+pub mod config;
+pub mod export;
+pub mod hooks;
+pub mod import;
+pub mod insight;
+pub mod instance;
+pub mod mini;
+pub mod overlay;
+pub mod portal;
+pub mod presence;
+pub use crate::mini::{MiniMsg, MiniOutput, MiniTimer};
+pub mod profile;
+pub mod ring;
+pub use crate::ring::ProgressRing;
+pub mod settings;
+pub use crate::settings::SettingsOutput;
+pub mod shortcuts;
+pub use crate::settings::{SettingsModel, SettingsMsg};
+pub mod state;
+pub mod storage;
+pub mod streak;
+pub mod theme;
 pub mod time;
 pub use crate::time::Time;
 pub mod timer;
-pub use crate::timer::{Timer, TimerMode, TimerMsg};
+pub use crate::timer::{Timer, TimerMode, TimerMsg, TimerOutput};
+pub mod tray;
+pub mod webhooks;
 use chrono::prelude::*;
 use gtk::prelude::*;
 use relm4::*;
 
-struct HeaderModel;
+struct HeaderModel {
+    mode: AppMode,
+    profiles: Vec<String>,
+    active: usize,
+}
+
+impl HeaderModel {
+    fn new(mode: AppMode) -> HeaderModel {
+        let profiles = profile::list();
+        let active = profile::active();
+        HeaderModel {
+            mode,
+            active: profiles.iter().position(|x| x == &active).unwrap_or(0),
+            profiles,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum HeaderMsg {
+    /// Profiles may have been added or switched from the command line
+    Refresh,
+    Select(AppMode),
+}
+
 #[derive(Debug)]
 enum HeaderOutput {
     FlowTime,
     Settings,
     Statistics,
+    Profile(String),
 }
 
 #[relm4::component]
 impl SimpleComponent for HeaderModel {
-    type Input = ();
-    type Init = ();
+    type Input = HeaderMsg;
+    type Init = AppMode;
     type Output = HeaderOutput;
 
     view! {
@@ -30,100 +80,88 @@ impl SimpleComponent for HeaderModel {
                 #[name = "group"]
                 gtk::ToggleButton {
                     set_label: "FlowTime",
-                    set_active: true,
+                    #[watch]
+                    #[block_signal(flowtime_handler)]
+                    set_active: model.mode == AppMode::FlowTime,
                     connect_toggled[sender] => move |btn| {
                         if btn.is_active() {
-                            sender.output(HeaderOutput::FlowTime).unwrap()
+                            sender.input(HeaderMsg::Select(AppMode::FlowTime))
                         }
-                    },
+                    } @flowtime_handler,
                 },
                 gtk::ToggleButton {
                     set_label: "Settings",
                     set_group: Some(&group),
+                    #[watch]
+                    #[block_signal(settings_handler)]
+                    set_active: model.mode == AppMode::Settings,
                     connect_toggled[sender] => move |btn| {
                         if btn.is_active() {
-                            sender.output(HeaderOutput::Settings).unwrap()
+                            sender.input(HeaderMsg::Select(AppMode::Settings))
                         }
-                    },
+                    } @settings_handler,
                 },
                 gtk::ToggleButton {
                     set_label: "Statistics",
                     set_group: Some(&group),
+                    #[watch]
+                    #[block_signal(statistics_handler)]
+                    set_active: model.mode == AppMode::Statistics,
                     connect_toggled[sender] => move |btn| {
                         if btn.is_active() {
-                            sender.output(HeaderOutput::Statistics).unwrap()
+                            sender.input(HeaderMsg::Select(AppMode::Statistics))
                         }
-                    },
+                    } @statistics_handler,
                 },
-            }
+            },
+            pack_end = &gtk::DropDown {
+                set_tooltip_text: Some("Settings profile"),
+                #[watch]
+                #[block_signal(profile_handler)]
+                set_model: Some(&gtk::StringList::new(
+                    &model.profiles.iter().map(String::as_str).collect::<Vec<_>>()
+                )),
+                #[watch]
+                #[block_signal(profile_handler)]
+                set_selected: model.active as u32,
+                connect_selected_notify[sender] => move |dropdown| {
+                    if let Some(item) = dropdown
+                        .selected_item()
+                        .and_then(|x| x.downcast::<gtk::StringObject>().ok())
+                    {
+                        sender.output(HeaderOutput::Profile(item.string().to_string())).unwrap()
+                    }
+                } @profile_handler,
+            },
         }
     }
     fn init(
-        _params: Self::Init,
+        params: Self::Init,
         root: &Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        let model = HeaderModel;
+        let model = HeaderModel::new(params);
         let widgets = view_output!();
 
         ComponentParts { model, widgets }
     }
-}
-
-#[derive(Debug, Clone)]
-struct SettingsModel;
-
-#[derive(Debug)]
-enum SettingsMsg {
-    SaveReset(bool),
-}
-
-#[relm4::component]
-impl SimpleComponent for SettingsModel {
-    type Input = ();
-    type Init = ();
-    type Output = SettingsMsg;
-
-    view! {
-        gtk::Box {
-            set_orientation: gtk::Orientation::Vertical,
-            set_spacing: 10,
-            gtk::Box {
-                set_spacing: 10,
-                gtk::Switch {
-                    set_active: cfg!().restart,
-                    connect_state_notify => move |switch| {
-                    confy::store("flowtime", Some("flowtime"), Config { restart: switch.is_active(), reset_save: cfg!().reset_save }).unwrap();
-                    },
-                },
-                gtk::Label {
-                    set_label: "Auto start Flowtime session after the break has ended."
-                }
-            },
-            gtk::Box {
-                set_spacing: 10,
-                gtk::Switch {
-                    set_active: cfg!().reset_save,
-                    connect_state_notify => move |switch| {
-                    confy::store("flowtime", Some("flowtime"), Config { restart: cfg!().restart,reset_save: switch.is_active() }).unwrap();
-                    },
-                },
-                gtk::Label {
-                    set_label: "Save the current work time to statistics when reset is pressed"
-                }
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            HeaderMsg::Refresh => *self = HeaderModel::new(self.mode),
+            HeaderMsg::Select(mode) => {
+                self.mode = mode;
+                sender
+                    .output(match mode {
+                        AppMode::FlowTime => HeaderOutput::FlowTime,
+                        AppMode::Settings => HeaderOutput::Settings,
+                        AppMode::Statistics => HeaderOutput::Statistics,
+                    })
+                    .unwrap()
             }
         }
     }
-    fn init(
-        _params: Self::Init,
-        root: &Self::Root,
-        _sender: ComponentSender<Self>,
-    ) -> ComponentParts<Self> {
-        let model = SettingsModel;
-        let widgets = view_output!();
-        ComponentParts { model, widgets }
-    }
 }
+
 #[derive(Debug, Clone)]
 struct StatsticsModel;
 
@@ -148,8 +186,7 @@ impl SimpleComponent for StatsticsModel {
         ComponentParts { model, widgets }
     }
 }
-
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum AppMode {
     FlowTime,
     Settings,
@@ -159,13 +196,42 @@ enum AppMode {
 #[derive(Debug)]
 enum MainAppMsg {
     SetMode(AppMode),
+    Export,
+    /// The config or statistics were changed by another process or by hand
+    Reloaded,
+    SwitchProfile(String),
+    /// Switches between this window and the mini window
+    ToggleMini,
+    /// Brings the full window back, e.g. from the tray icon or a second launch
+    ShowWindow,
+    /// The window was closed while running in background
+    Hide,
+    Quit,
+    ToggleFlowTime,
+    /// Passed on to the timer, e.g. from a global hotkey
+    Timer(TimerMsg),
+    Shortcut(shortcuts::Action),
+    /// The settings page saved the config
+    ConfigSaved,
 }
 
 struct MainApp {
+    window: gtk::ApplicationWindow,
     mode: AppMode,
     header: Controller<HeaderModel>,
     main: Controller<Timer>,
+    mini: Controller<MiniTimer>,
+    /// Whether the mini window is shown instead of this one
+    mini_mode: bool,
+    /// Whether the window was closed with `run_in_background` on
+    hidden: bool,
     setting: Controller<SettingsModel>,
+    /// Of the main and the mini window, rebound whenever the config changes
+    shortcuts: Vec<gtk::ShortcutController>,
+    work_ring: ProgressRing,
+    break_ring: ProgressRing,
+    // native dialogs have to be kept alive while they are shown
+    export_dialog: Option<gtk::FileChooserNative>,
 }
 
 #[relm4::component]
@@ -178,6 +244,16 @@ impl SimpleComponent for MainApp {
             set_titlebar: Some(model.header.widget()),
             set_title: Some("Flowtime"),
             set_default_size: (386,311),
+            #[watch]
+            set_visible: !model.mini_mode && !model.hidden,
+            connect_close_request[sender] => move |_| {
+                if cfg!().run_in_background {
+                    sender.input(MainAppMsg::Hide);
+                    gtk::Inhibit(true)
+                } else {
+                    gtk::Inhibit(false)
+                }
+            },
             gtk::Box {
                 set_valign: gtk::Align::Center,
                 set_halign: gtk::Align::Center,
@@ -192,127 +268,573 @@ impl SimpleComponent for MainApp {
                     model.setting.widget(),
                 },
                 gtk::Box {
-                    set_orientation: gtk::Orientation::Horizontal,
-                        set_spacing: 3,
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 10,
                     #[watch]
                     set_visible: matches!(model.mode, AppMode::Statistics),
 
                     gtk::Box {
-                        gtk::Label {
-                            set_label: "Work"
-                        },
-                        add_css_class: "statcircle",
-                        set_orientation: gtk::Orientation::Vertical,
-                        set_valign: gtk::Align::Center,
-                        gtk::Label {
-                            #[watch]
-                            set_label:    &second_to_formatted(current_stat(*CURRENT_MONTH).2),
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 3,
+
+                        #[local_ref]
+                        work_ring -> gtk::Overlay {
+                            add_overlay = &gtk::Box {
+                                gtk::Label {
+                                    set_label: "Work"
+                                },
+                                set_orientation: gtk::Orientation::Vertical,
+                                set_valign: gtk::Align::Center,
+                                gtk::Label {
+                                    #[watch]
+                                    set_label:    &second_to_formatted(current_stat(*CURRENT_MONTH).2),
+                                },
+                                gtk::Label {
+                                    #[watch]
+                                    set_label:
+                                        if current_stat(*CURRENT_MONTH).2 < 60 {
+                                            "second"
+                                        } else if current_stat(*CURRENT_MONTH).2 < 3600 {
+                                            "minute"
+                                        } else {
+                                            "hour"
+                                        }
+                                },
+                            },
                         },
-                        gtk::Label {
-                            #[watch]
-                            set_label:
-                                if current_stat(*CURRENT_MONTH).2 < 60 {
-                                    "second"
-                                } else if current_stat(*CURRENT_MONTH).2 < 3600 {
-                                    "minute"
-                                } else {
-                                    "hour"
-                                }
+                        #[local_ref]
+                        break_ring -> gtk::Overlay {
+                            add_overlay = &gtk::Box {
+                                set_orientation: gtk::Orientation::Vertical,
+                                set_valign: gtk::Align::Center,
+                                gtk::Label {
+                                    set_label: "Break"
+                                },
+                                gtk::Label {
+                                    #[watch]
+                                    set_label:    &second_to_formatted(current_stat(*CURRENT_MONTH).1)
+                                },
+                                gtk::Label {
+                                    #[watch]
+                                    set_label:
+                                        if current_stat(*CURRENT_MONTH).1 < 60 {
+                                            "second"
+                                        } else if current_stat(*CURRENT_MONTH).1 < 3600 {
+                                            "minute"
+                                        } else {
+                                            "hour"
+                                        }
+                                },
+                            },
                         },
                     },
-                    gtk::Box {
-                        add_css_class: "statcircle",
-                        set_orientation: gtk::Orientation::Vertical,
-                        set_valign: gtk::Align::Center,
-                        gtk::Label {
-                            set_label: "Break"
+                    gtk::Label {
+                        #[watch]
+                        set_label: &format!(
+                            "Today: {}",
+                            goal_progress(day_work(today()), daily_goal(today()))
+                        ),
+                    },
+                    gtk::ProgressBar {
+                        #[watch]
+                        set_visible: daily_goal(today()) != 0,
+                        #[watch]
+                        set_fraction: goal_fraction(day_work(today()), daily_goal(today())),
+                    },
+                    gtk::Label {
+                        #[watch]
+                        set_label: &format!(
+                            "This week: {}",
+                            goal_progress(week_work(today()), cfg!().weekly_goal_minutes)
+                        ),
+                    },
+                    gtk::ProgressBar {
+                        #[watch]
+                        set_visible: cfg!().weekly_goal_minutes != 0,
+                        #[watch]
+                        set_fraction: goal_fraction(week_work(today()), cfg!().weekly_goal_minutes),
+                    },
+                    gtk::Label {
+                        #[watch]
+                        set_label: &{
+                            let streaks = current_streaks();
+                            format!(
+                                "Current streak: {} days, longest: {} days",
+                                streaks.current, streaks.longest
+                            )
                         },
-                        gtk::Label {
-                            #[watch]
-                            set_label:    &second_to_formatted(current_stat(*CURRENT_MONTH).1)
+                    },
+                    gtk::Label {
+                        #[watch]
+                        set_label: &consistency_line(),
+                    },
+                    #[name = "heatmap"]
+                    gtk::Grid {
+                        set_halign: gtk::Align::Center,
+                        set_row_spacing: 2,
+                        set_column_spacing: 2,
+                    },
+                    gtk::Label {
+                        #[watch]
+                        set_label: &match insight::best_window(&insight::hourly_work(&storage::sessions(None, None))) {
+                            Some(start) => format!("Best focus window: {}", insight::window_string(start)),
+                            None => "Best focus window: not enough sessions yet".to_string(),
                         },
-                        gtk::Label {
-                            #[watch]
-                            set_label:
-                                if current_stat(*CURRENT_MONTH).1 < 60 {
-                                    "second"
-                                } else if current_stat(*CURRENT_MONTH).1 < 3600 {
-                                    "minute"
-                                } else {
-                                    "hour"
-                                }
+                    },
+                    #[name = "hourly"]
+                    gtk::Box {
+                        set_halign: gtk::Align::Center,
+                        set_spacing: 2,
+                        set_height_request: 60,
+                    },
+                    #[name = "weekday"]
+                    gtk::Box {
+                        set_halign: gtk::Align::Center,
+                        set_spacing: 2,
+                        set_height_request: 60,
+                    },
+                    gtk::Button {
+                        set_label: "Export",
+                        set_halign: gtk::Align::Center,
+                        set_tooltip_text: Some("Save sessions as .csv, .json or .ics"),
+                        connect_clicked => MainAppMsg::Export,
+                    },
+                    gtk::Label {
+                        #[watch]
+                        set_label: &format!(
+                            "Fragmented sessions (under {} min): {}",
+                            cfg!().min_focus_minutes,
+                            current_fragmented(*CURRENT_MONTH)
+                        ),
+                    },
+                    gtk::Label {
+                        #[watch]
+                        set_label: &if cfg!().max_flow_minutes == 0 {
+                            "Long session nudge: disabled".to_string()
+                        } else {
+                            format!("Long session nudge: after {} min", cfg!().max_flow_minutes)
                         },
                     },
                 }
             }
         }
     }
+    // the heatmap cells depend on the statistics, so they are rebuilt on every update
+    fn post_view() {
+        fill_heatmap(heatmap);
+        let sessions = storage::sessions(None, None);
+        fill_hourly(hourly, &sessions);
+        fill_weekday(weekday, &sessions);
+        // the rings split the month between work and break
+        let (_, break_second, work_second) = current_stat(*CURRENT_MONTH);
+        let total = (break_second + work_second).max(1) as f64;
+        model.work_ring.set_fraction(work_second as f64 / total);
+        model.break_ring.set_fraction(break_second as f64 / total);
+    }
     fn init(
         params: Self::Init,
         root: &Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        let model = MainApp {
+        let mini =
+            MiniTimer::builder()
+                .launch(())
+                .forward(sender.input_sender(), |msg| match msg {
+                    MiniOutput::ToggleFlowTime => MainAppMsg::ToggleFlowTime,
+                    MiniOutput::Expand => MainAppMsg::ToggleMini,
+                });
+        let tray = cfg!().tray.then(|| {
+            let tray_sender = sender.clone();
+            tray::Tray::new(move |action| {
+                tray_sender.input(match action {
+                    tray::TrayAction::Toggle => MainAppMsg::ToggleFlowTime,
+                    tray::TrayAction::Break => MainAppMsg::Timer(TimerMsg::ToggleBreak),
+                    tray::TrayAction::Reset => MainAppMsg::Timer(TimerMsg::ResetSession),
+                    tray::TrayAction::Show => MainAppMsg::ShowWindow,
+                    tray::TrayAction::Quit => MainAppMsg::Quit,
+                })
+            })
+        });
+        // straight to the mini window and the tray, redrawing this one every second is wasteful
+        let main = Timer::builder()
+            .launch(TimerMode::Stop)
+            .forward(mini.sender(), move |msg| match msg {
+                TimerOutput::Changed {
+                    mode,
+                    time,
+                    second,
+                    session_work,
+                    fraction,
+                } => {
+                    api::update(&mode, second, &time);
+                    instance::set_session_work(session_work);
+                    if let Some(tray) = &tray {
+                        tray.update(&mode, &time);
+                    }
+                    MiniMsg::Update {
+                        mode,
+                        time,
+                        fraction,
+                    }
+                }
+            });
+        let mut model = MainApp {
+            window: root.clone(),
             mode: params,
-            header: HeaderModel::builder().launch(()).forward(
-                sender.input_sender(),
-                |msg| match msg {
+            header: HeaderModel::builder()
+                .launch(params)
+                .forward(sender.input_sender(), |msg| match msg {
                     HeaderOutput::FlowTime => MainAppMsg::SetMode(AppMode::FlowTime),
                     HeaderOutput::Settings => MainAppMsg::SetMode(AppMode::Settings),
                     HeaderOutput::Statistics => MainAppMsg::SetMode(AppMode::Statistics),
-                },
-            ),
-            main: Timer::builder().launch(TimerMode::Stop).detach(),
-            setting: SettingsModel::builder().launch(()).detach(),
+                    HeaderOutput::Profile(name) => MainAppMsg::SwitchProfile(name),
+                }),
+            main,
+            mini,
+            mini_mode: false,
+            hidden: false,
+            setting: SettingsModel::builder()
+                .launch(())
+                .forward(sender.input_sender(), |msg| match msg {
+                    SettingsOutput::Saved => MainAppMsg::ConfigSaved,
+                }),
+            shortcuts: vec![],
+            work_ring: ProgressRing::new(180),
+            break_ring: ProgressRing::new(180),
+            export_dialog: None,
         };
+        let work_ring = model.work_ring.widget();
+        let break_ring = model.break_ring.widget();
         let widgets = view_output!();
+        let reload_sender = sender.clone();
+        state::watch(move || reload_sender.input(MainAppMsg::Reloaded));
+        for window in [root.upcast_ref::<gtk::Window>(), model.mini.widget()] {
+            let controller = gtk::ShortcutController::new();
+            window.add_controller(&controller);
+            model.shortcuts.push(controller);
+        }
+        model.bind_shortcuts(&sender);
+        if cfg!().global_shortcuts {
+            let hotkey_sender = sender.clone();
+            portal::register(move |msg| hotkey_sender.input(MainAppMsg::Timer(msg)));
+        }
+        // activating the app again, e.g. by launching it a second time, brings the window back
+        let app = relm4::main_application();
+        let activate_sender = sender.clone();
+        app.connect_activate(move |_| activate_sender.input(MainAppMsg::ShowWindow));
+        for name in instance::ACTIONS {
+            let action = gtk::gio::SimpleAction::new(name, None);
+            let action_sender = sender.clone();
+            action.connect_activate(move |_, _| action_sender.input(action_message(name)));
+            app.add_action(&action);
+        }
+        let action =
+            gtk::gio::SimpleAction::new(instance::PROFILE, Some(gtk::glib::VariantTy::STRING));
+        let profile_sender = sender.clone();
+        action.connect_activate(move |_, target| {
+            if let Some(name) = target.and_then(|x| x.get::<String>()) {
+                profile_sender.input(MainAppMsg::SwitchProfile(name));
+            }
+        });
+        app.add_action(&action);
+        instance::add_session_work();
+        fill_heatmap(&widgets.heatmap);
+        let sessions = storage::sessions(None, None);
+        fill_hourly(&widgets.hourly, &sessions);
+        fill_weekday(&widgets.weekday, &sessions);
+        theme::init();
+        webhooks::start();
+        if cfg!().api_port != 0 {
+            let api_sender = sender.clone();
+            api::start(cfg!().api_port, move |msg| {
+                api_sender.input(MainAppMsg::Timer(msg))
+            });
+        }
         relm4::set_global_css(
             r#"
-            .statcircle {
-              border-width: 8px;
-              border-color: @accent_color;
-              border-style: inset dotted;
-              border-radius: 100%;
-              box-shadow: 0px 1px 6px rgba(0, 0, 0, 0.07);
-              padding: 100px;
+            .ring {
+              color: @accent_color;
+              margin: 6px;
             }
+            .heat0 { background-color: alpha(@accent_color, 0.08); }
+            .heat1 { background-color: alpha(@accent_color, 0.3); }
+            .heat2 { background-color: alpha(@accent_color, 0.5); }
+            .heat3 { background-color: alpha(@accent_color, 0.75); }
+            .heat4 { background-color: @accent_color; }
             "#,
         );
         ComponentParts { model, widgets }
     }
-    fn update(&mut self, msg: Self::Input, _sender: ComponentSender<Self>) {
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
         match msg {
             MainAppMsg::SetMode(mode) => {
                 self.mode = mode;
             }
+            MainAppMsg::Export => {
+                let dialog = gtk::FileChooserNative::new(
+                    Some("Export statistics"),
+                    None::<&gtk::Window>,
+                    gtk::FileChooserAction::Save,
+                    Some("Export"),
+                    Some("Cancel"),
+                );
+                dialog.set_current_name("flowtime.csv");
+                dialog.connect_response(|dialog, response| {
+                    if response == gtk::ResponseType::Accept {
+                        if let Some(path) = dialog.file().and_then(|file| file.path()) {
+                            let exported = export::export(
+                                &storage::sessions(None, None),
+                                export::Format::from_path(&path),
+                            );
+                            if let Err(e) = exported.and_then(|x| Ok(std::fs::write(&path, x)?)) {
+                                notify("export", "Export failed", &e.to_string());
+                            }
+                        }
+                    }
+                });
+                dialog.show();
+                self.export_dialog = Some(dialog);
+            }
+            MainAppMsg::Reloaded => {
+                theme::apply();
+                self.bind_shortcuts(&sender);
+                self.header.emit(HeaderMsg::Refresh);
+                self.setting.emit(SettingsMsg::Reload);
+                self.main.emit(TimerMsg::Refresh);
+            }
+            MainAppMsg::SwitchProfile(name) => {
+                if name != profile::active() {
+                    if let Err(e) = profile::switch(&name) {
+                        notify("profile", "Failed to switch profile", &e.to_string());
+                    }
+                    theme::apply();
+                    self.bind_shortcuts(&sender);
+                    // the switch may come from another process
+                    self.header.emit(HeaderMsg::Refresh);
+                    self.setting.emit(SettingsMsg::Reload);
+                    self.main.emit(TimerMsg::Refresh);
+                }
+            }
+            MainAppMsg::ToggleMini => {
+                self.mini_mode = !self.mini_mode;
+                self.mini.emit(MiniMsg::SetVisible(self.mini_mode));
+            }
+            MainAppMsg::ShowWindow => {
+                if self.mini_mode {
+                    self.mini_mode = false;
+                    self.mini.emit(MiniMsg::SetVisible(false));
+                }
+                self.hidden = false;
+                self.window.present();
+            }
+            MainAppMsg::Hide => self.hidden = true,
+            MainAppMsg::Quit => relm4::main_application().quit(),
+            MainAppMsg::ToggleFlowTime => self.main.emit(TimerMsg::ToggleFlowTime),
+            MainAppMsg::Timer(msg) => self.main.emit(msg),
+            MainAppMsg::Shortcut(action) => match action {
+                shortcuts::Action::Toggle => self.main.emit(TimerMsg::ToggleFlowTime),
+                shortcuts::Action::Break => self.main.emit(TimerMsg::ToggleBreak),
+                shortcuts::Action::Reset => self.main.emit(TimerMsg::ResetSession),
+                shortcuts::Action::FlowTime => {
+                    self.header.emit(HeaderMsg::Select(AppMode::FlowTime))
+                }
+                shortcuts::Action::Settings => {
+                    self.header.emit(HeaderMsg::Select(AppMode::Settings))
+                }
+                shortcuts::Action::Statistics => {
+                    self.header.emit(HeaderMsg::Select(AppMode::Statistics))
+                }
+                shortcuts::Action::Mini => sender.input(MainAppMsg::ToggleMini),
+                shortcuts::Action::Help => shortcuts::window().show(),
+            },
+            MainAppMsg::ConfigSaved => {
+                theme::apply();
+                self.bind_shortcuts(&sender);
+            }
+        }
+    }
+}
+
+impl MainApp {
+    fn bind_shortcuts(&self, sender: &ComponentSender<MainApp>) {
+        let sender = sender.clone();
+        let run: std::rc::Rc<dyn Fn(shortcuts::Action)> =
+            std::rc::Rc::new(move |action| sender.input(MainAppMsg::Shortcut(action)));
+        for controller in self.shortcuts.iter() {
+            shortcuts::bind(controller, run.clone());
         }
     }
 }
 
 use serde_derive::{Deserialize, Serialize};
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 struct Config {
+    /// Schema version of the file, see `config::VERSION`
+    version: u32,
     restart: bool,
     reset_save: bool,
+    /// Whether closing the window only hides it, so the timer and the tray icon keep running
+    run_in_background: bool,
+    /// Breaks last a `1 / break_ratio` of the work time before them
+    break_ratio: u32,
+    /// Whether breaks cover the screen until they are over, skipped or postponed
+    break_overlay: bool,
+    /// Whether the break overlay covers every monitor instead of just one
+    break_overlay_every_monitor: bool,
+    /// One of them is shown on the break overlay
+    break_messages: Vec<String>,
+    /// Seconds into a break before the overlay can be skipped, 0 allows it right away
+    strict_break_seconds: u32,
+    /// How long the overlay's postpone button holds a break, 0 hides the button
+    postpone_minutes: u32,
+    /// Played when a break ends or a nudge is sent, empty for the bundled tone
+    sound: std::path::PathBuf,
+    /// Whether to play `sound` at all
+    sounds: bool,
+    /// Whether to send desktop notifications
+    notifications: bool,
+    /// Work sessions shorter than this are counted as fragmented, 0 disables it
+    min_focus_minutes: u32,
+    /// Continuous working minutes before nudging the user to take a break, 0 disables it
+    max_flow_minutes: u32,
+    /// Idle minutes before work pauses itself, 0 disables it
+    idle_minutes: u32,
+    /// Whether locking the session during work starts the break
+    break_on_lock: bool,
+    /// Focus time goal for a day, 0 disables it
+    daily_goal_minutes: u32,
+    /// Focus time goal for an ISO week, 0 disables it
+    weekly_goal_minutes: u32,
+    /// Per weekday overrides of `daily_goal_minutes`, as (days from monday, minutes)
+    weekday_goal_minutes: Vec<(u32, u32)>,
+    /// Whether streaks need the daily goal to be met instead of any work
+    streak_requires_goal: bool,
+    /// Colors of the work, break and pause stages
+    theme: theme::Theme,
+    color_scheme: theme::ColorScheme,
+    /// Whether to register the global hotkeys with the desktop portal on start
+    global_shortcuts: bool,
+    /// Whether to show the StatusNotifierItem tray icon
+    tray: bool,
+    /// URLs getting a POST with the JSON of every timer event
+    webhooks: Vec<String>,
+    /// Port of the HTTP API on localhost, 0 disables it
+    api_port: u16,
+    /// Keyboard shortcuts as GTK accelerators, tables like this one have to stay last
+    shortcuts: shortcuts::Shortcuts,
+    /// Shell commands run on timer events, a table as well
+    hooks: hooks::Hooks,
 }
 impl std::default::Default for Config {
     fn default() -> Self {
         Self {
+            version: config::VERSION,
             restart: false,
             reset_save: true,
+            run_in_background: false,
+            break_ratio: 5,
+            break_overlay: false,
+            break_overlay_every_monitor: false,
+            break_messages: vec![
+                "Stand up and stretch your arms over your head.".to_string(),
+                "Look at something far away for twenty seconds.".to_string(),
+                "Roll your shoulders and loosen your neck.".to_string(),
+                "Get a glass of water.".to_string(),
+                "Take a short walk.".to_string(),
+            ],
+            strict_break_seconds: 0,
+            postpone_minutes: 5,
+            sound: Default::default(),
+            sounds: true,
+            notifications: true,
+            min_focus_minutes: 15,
+            max_flow_minutes: 90,
+            idle_minutes: 5,
+            break_on_lock: true,
+            daily_goal_minutes: 300,
+            weekly_goal_minutes: 1500,
+            weekday_goal_minutes: vec![(5, 0), (6, 0)],
+            streak_requires_goal: true,
+            theme: theme::Theme::Adwaita,
+            color_scheme: theme::ColorScheme::System,
+            global_shortcuts: true,
+            tray: true,
+            webhooks: vec![],
+            api_port: 0,
+            shortcuts: Default::default(),
+            hooks: Default::default(),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl Config {
+    fn sound(&self) -> std::path::PathBuf {
+        if self.sound.as_os_str().is_empty() {
+            "tone.wav".into()
+        } else {
+            self.sound.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DayStat {
+    date: NaiveDate,
+    break_second: u32,
+    work_second: u32,
+}
+
+/// A continuous stretch of work, ended by a break or a reset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Session {
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+    work_second: u32,
+    /// Break time earned by the session
+    #[serde(default)]
+    break_second: u32,
+    #[serde(default)]
+    tag: String,
+    /// Settings profile active when the session ended
+    #[serde(default)]
+    profile: String,
+}
+
+/// Something that broke the focus, logged with a global shortcut
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interruption {
+    time: DateTime<Local>,
+    /// Tag of the session it interrupted
+    note: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct Stats {
     month_break_work: Vec<(u32, u32, u32)>,
+    #[serde(default)]
+    month_fragmented: Vec<(u32, u32)>,
+    /// Where days and sessions of every profile are stored, `None` until chosen
+    #[serde(default)]
+    storage: Option<storage::Backend>,
+    // tables have to be serialized after plain values in toml
+    #[serde(default)]
+    days: Vec<DayStat>,
+    #[serde(default)]
+    sessions: Vec<Session>,
+    #[serde(default)]
+    interruptions: Vec<Interruption>,
 }
 
 impl std::default::Default for Stats {
     fn default() -> Self {
         Self {
             month_break_work: vec![(*CURRENT_MONTH, 0, 0)],
+            month_fragmented: vec![],
+            storage: None,
+            days: vec![],
+            sessions: vec![],
+            interruptions: vec![],
         }
     }
 }
@@ -330,29 +852,258 @@ fn current_stat(month: u32) -> (u32, u32, u32) {
     {
         Some(x) => *x,
         None => {
-            confy::store(
-                "flowtime",
-                Some("statistics"),
-                Stats {
-                    month_break_work: new_struct,
-                },
-            )
-            .unwrap();
+            report_save(state::save_stats(Stats {
+                month_break_work: new_struct,
+                ..stat!()
+            }));
             (*CURRENT_MONTH, 0, 0)
         }
     }
 }
+
+fn current_fragmented(month: u32) -> u32 {
+    stat!()
+        .month_fragmented
+        .iter()
+        .find(|(m, _)| m == &month)
+        .map(|(_, count)| *count)
+        .unwrap_or(0)
+}
+
+fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+fn day_work(date: NaiveDate) -> u32 {
+    storage::days()
+        .iter()
+        .find(|day| day.date == date)
+        .map(|day| day.work_second)
+        .unwrap_or(0)
+}
+
+fn week_work(date: NaiveDate) -> u32 {
+    storage::days()
+        .iter()
+        .filter(|day| day.date.iso_week() == date.iso_week())
+        .map(|day| day.work_second)
+        .sum()
+}
+
+/// The goal in minutes for the given day, taking weekday overrides into account
+fn daily_goal(date: NaiveDate) -> u32 {
+    goal_for(&cfg!(), date)
+}
+
+fn goal_for(config: &Config, date: NaiveDate) -> u32 {
+    config
+        .weekday_goal_minutes
+        .iter()
+        .find(|(weekday, _)| *weekday == date.weekday().num_days_from_monday())
+        .map(|(_, minutes)| *minutes)
+        .unwrap_or(config.daily_goal_minutes)
+}
+
+fn current_streaks() -> streak::Streaks {
+    let config = cfg!();
+    streak::streaks(
+        &storage::days(),
+        today(),
+        |date| goal_for(&config, date),
+        config.streak_requires_goal,
+    )
+}
+
+/// Weekday consistency, e.g. "Mon 80% Tue 100% Wed - ..."
+fn consistency_line() -> String {
+    let config = cfg!();
+    let consistency = streak::weekday_consistency(
+        &storage::days(),
+        today(),
+        |date| goal_for(&config, date),
+        config.streak_requires_goal,
+    );
+    ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]
+        .iter()
+        .zip(consistency.iter())
+        .map(|(weekday, fraction)| match fraction {
+            Some(fraction) => format!("{} {:.0}%", weekday, fraction * 100.0),
+            None => format!("{} -", weekday),
+        })
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+/// Weeks shown in the statistics heatmap
+const HEATMAP_WEEKS: i64 = 20;
+
+/// Fills the grid with one cell per day, a column per week and a row per weekday
+fn fill_heatmap(grid: &gtk::Grid) {
+    while let Some(child) = grid.first_child() {
+        grid.remove(&child);
+    }
+    let config = cfg!();
+    let days = storage::days();
+    let today = today();
+    let first_monday = today
+        - chrono::Duration::days(today.weekday().num_days_from_monday() as i64)
+        - chrono::Duration::weeks(HEATMAP_WEEKS - 1);
+    for offset in 0..HEATMAP_WEEKS * 7 {
+        let date = first_monday + chrono::Duration::days(offset);
+        if date > today {
+            break;
+        }
+        let work_second = days
+            .iter()
+            .find(|day| day.date == date)
+            .map(|day| day.work_second)
+            .unwrap_or(0);
+        let cell = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        cell.set_size_request(12, 12);
+        cell.add_css_class(&format!(
+            "heat{}",
+            streak::heat_level(work_second, goal_for(&config, date))
+        ));
+        cell.set_tooltip_text(Some(&format!("{}: {}", date, hour_minute(work_second))));
+        grid.attach(&cell, (offset / 7) as i32, (offset % 7) as i32, 1, 1);
+    }
+}
+
+fn goal_fraction(work_second: u32, goal_minutes: u32) -> f64 {
+    if goal_minutes == 0 {
+        0.0
+    } else {
+        (work_second as f64 / (goal_minutes as f64 * 60.0)).min(1.0)
+    }
+}
+
+fn hour_minute(t: u32) -> String {
+    let (hour, minute) = (t / 3600, (t % 3600) / 60);
+    if hour == 0 {
+        format!("{}m", minute)
+    } else if minute == 0 {
+        format!("{}h", hour)
+    } else {
+        format!("{}h {}m", hour, minute)
+    }
+}
+
+/// Formats progress towards a goal, e.g. "3h 10m / 5h"
+fn goal_progress(work_second: u32, goal_minutes: u32) -> String {
+    if goal_minutes == 0 {
+        hour_minute(work_second)
+    } else {
+        format!(
+            "{} / {}",
+            hour_minute(work_second),
+            hour_minute(goal_minutes * 60)
+        )
+    }
+}
+
+/// A single line summary of today's and this week's progress, meant for status bars.
+/// The session of a running instance counts as well, otherwise only saved work does.
+fn status_line() -> String {
+    let running = instance::session_work().unwrap_or(0);
+    format!(
+        "{} | week {}",
+        goal_progress(day_work(today()) + running, daily_goal(today())),
+        goal_progress(week_work(today()) + running, cfg!().weekly_goal_minutes)
+    )
+}
+
+/// Fills the box with one bar per hour of the day showing when work happened
+fn fill_hourly(container: &gtk::Box, sessions: &[Session]) {
+    while let Some(child) = container.first_child() {
+        container.remove(&child);
+    }
+    let hourly = insight::hourly_work(sessions);
+    let average = insight::average_session_by_hour(sessions);
+    let max = hourly.iter().max().copied().unwrap_or(0).max(1);
+    for (hour, work) in hourly.iter().enumerate() {
+        let bar = gtk::LevelBar::new();
+        bar.set_orientation(gtk::Orientation::Vertical);
+        bar.set_inverted(true);
+        bar.set_value(*work as f64 / max as f64);
+        bar.set_tooltip_text(Some(&format!(
+            "{:02}:00  {} worked, average session {}",
+            hour,
+            hour_minute(*work),
+            average[hour]
+                .map(hour_minute)
+                .unwrap_or_else(|| "-".to_string())
+        )));
+        container.append(&bar);
+    }
+}
+
+/// Fills the box with one bar per weekday, from monday, showing on which days work happened
+fn fill_weekday(container: &gtk::Box, sessions: &[Session]) {
+    while let Some(child) = container.first_child() {
+        container.remove(&child);
+    }
+    let weekday = insight::weekday_work(sessions);
+    let max = weekday.iter().max().copied().unwrap_or(0).max(1);
+    for (name, work) in ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]
+        .iter()
+        .zip(weekday.iter())
+    {
+        let bar = gtk::LevelBar::new();
+        bar.set_orientation(gtk::Orientation::Vertical);
+        bar.set_inverted(true);
+        bar.set_value(*work as f64 / max as f64);
+        bar.set_tooltip_text(Some(&format!("{}  {} worked", name, hour_minute(*work))));
+        let label = gtk::Label::new(Some(name));
+        label.add_css_class("caption");
+        let column = gtk::Box::new(gtk::Orientation::Vertical, 2);
+        column.set_width_request(24);
+        bar.set_vexpand(true);
+        column.append(&bar);
+        column.append(&label);
+        container.append(&column);
+    }
+}
+
+/// What the app action `name` of `instance::ACTIONS` does
+fn action_message(name: &str) -> MainAppMsg {
+    match name {
+        "toggle" => MainAppMsg::ToggleFlowTime,
+        "break" => MainAppMsg::Timer(TimerMsg::ToggleBreak),
+        "reset" => MainAppMsg::Timer(TimerMsg::ResetSession),
+        "interruption" => MainAppMsg::Timer(TimerMsg::LogInterruption),
+        "show" => MainAppMsg::ShowWindow,
+        "quit" => MainAppMsg::Quit,
+        _ => unreachable!("unknown app action {}", name),
+    }
+}
+
+fn notify(id: &str, title: &str, body: &str) {
+    if !cfg!().notifications {
+        return;
+    }
+    let notification = gtk::gio::Notification::new(title);
+    notification.set_body(Some(body));
+    relm4::main_application().send_notification(Some(id), &notification);
+}
+
+/// Tells about a statistics write that failed, the timer itself goes on
+fn report_save(result: anyhow::Result<()>) {
+    if let Err(e) = result {
+        eprintln!("flowtime: {:#}", e);
+        notify("save", "Failed to save statistics", &format!("{:#}", e));
+    }
+}
 #[macro_export]
 macro_rules! stat {
     () => {
-        confy::load::<Stats>("flowtime", Some("statistics")).unwrap()
+        $crate::state::stats()
     };
 }
 
 #[macro_export]
 macro_rules! cfg {
     () => {
-        confy::load::<Config>("flowtime", Some("flowtime")).unwrap()
+        $crate::state::config()
     };
 }
 
@@ -398,7 +1149,24 @@ lazy_static! {
     static ref CURRENT_MONTH: u32 = Utc::now().month();
 }
 
-fn main() {
-    let app = RelmApp::new("Flowtime");
-    app.run::<MainApp>(AppMode::FlowTime);
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        // prints the progress once, e.g. for waybar or polybar custom modules
+        Some("status") => println!("{}", status_line()),
+        Some("insights") => print!("{}", insight::report(&storage::sessions(None, None))),
+        Some("export") => export::command(&args[2..])?,
+        Some("import") => import::command(&args[2..])?,
+        Some("config") => config::command(&args[2..])?,
+        Some("profile") => profile::command(&args[2..])?,
+        // `--toggle`, `--profile NAME` etc. are forwarded to the running instance
+        _ => {
+            let command = instance::command(&args[1..])?;
+            let app = RelmApp::new(instance::APP_ID);
+            if !instance::forward(command)? {
+                app.run::<MainApp>(AppMode::FlowTime);
+            }
+        }
+    }
+    Ok(())
 }
@@ -0,0 +1,194 @@
+//! Compact window with just the clock of the timer.
+//!
+//! It only mirrors what `Timer` sends through `TimerOutput`, so both views always show the
+//! same session. GTK 4 can't keep a window above others by itself, so on X11 the window
+//! manager is asked for `_NET_WM_STATE_ABOVE` directly. Wayland has no protocol for it,
+//! there the compositor's window rules or "Always on Top" have to do.
+use crate::ring::ProgressRing;
+use crate::theme;
+use crate::timer::{stage_name, TimerMode};
+use gtk::prelude::*;
+use relm4::*;
+
+#[derive(Debug)]
+pub struct MiniTimer {
+    mode: TimerMode,
+    time: String,
+    ring: ProgressRing,
+    visible: bool,
+}
+
+#[derive(Debug)]
+pub enum MiniMsg {
+    Update {
+        mode: TimerMode,
+        time: String,
+        fraction: f64,
+    },
+    SetVisible(bool),
+}
+
+#[derive(Debug)]
+pub enum MiniOutput {
+    ToggleFlowTime,
+    /// Go back to the full window
+    Expand,
+}
+
+/// Asks an EWMH window manager to keep the mapped `window` above the others
+#[cfg(feature = "x11")]
+fn keep_above(window: &gtk::Window) {
+    use gdk4_x11::{X11Display, X11Surface};
+    use x11::xlib;
+
+    let (display, surface) = match (
+        window.display().downcast::<X11Display>(),
+        window.surface().downcast::<X11Surface>(),
+    ) {
+        (Ok(display), Ok(surface)) => (display, surface),
+        // not on X11
+        _ => return,
+    };
+    unsafe {
+        let xdisplay = display.xdisplay();
+        let atom = |name: &[u8]| xlib::XInternAtom(xdisplay, name.as_ptr() as *const _, 0);
+        let mut event: xlib::XClientMessageEvent = std::mem::zeroed();
+        event.type_ = xlib::ClientMessage;
+        event.window = surface.xid();
+        event.message_type = atom(b"_NET_WM_STATE\0");
+        event.format = 32;
+        // add the state, as asked by a normal application
+        event.data.set_long(0, 1);
+        event.data.set_long(1, atom(b"_NET_WM_STATE_ABOVE\0") as _);
+        event.data.set_long(3, 1);
+        xlib::XSendEvent(
+            xdisplay,
+            display.xrootwindow(),
+            xlib::False,
+            xlib::SubstructureRedirectMask | xlib::SubstructureNotifyMask,
+            &mut event as *mut xlib::XClientMessageEvent as *mut xlib::XEvent,
+        );
+        xlib::XFlush(xdisplay);
+    }
+}
+
+#[cfg(not(feature = "x11"))]
+fn keep_above(_window: &gtk::Window) {}
+
+#[relm4::component(pub)]
+impl SimpleComponent for MiniTimer {
+    type Input = MiniMsg;
+    type Init = ();
+    type Output = MiniOutput;
+
+    view! {
+        gtk::Window {
+            set_title: Some("Flowtime"),
+            set_decorated: false,
+            set_resizable: false,
+            add_css_class: "mini",
+            #[watch]
+            set_visible: model.visible,
+            // the window manager forgets the state once the window is hidden
+            connect_map => move |window| keep_above(window),
+
+            // lets the undecorated window be dragged around
+            gtk::WindowHandle {
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 6,
+                    set_margin_all: 6,
+                    #[watch]
+                    set_css_classes: &[theme::stage_class(&model.mode)],
+
+                    #[local_ref]
+                    mini_ring -> gtk::Overlay {
+                        add_overlay = &gtk::Box {
+                            set_orientation: gtk::Orientation::Vertical,
+                            set_valign: gtk::Align::Center,
+
+                            gtk::Label {
+                                add_css_class: "mode",
+                                #[watch]
+                                set_visible: !matches!(&model.mode, TimerMode::Stop),
+                                #[watch]
+                                set_label: stage_name(&model.mode),
+                            },
+                            gtk::Label {
+                                add_css_class: "clock",
+                                #[watch]
+                                set_label: &model.time,
+                            },
+                        },
+                    },
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_valign: gtk::Align::Center,
+                        set_spacing: 6,
+
+                        gtk::Button {
+                            add_css_class: "circular",
+                            #[watch]
+                            set_icon_name: match &model.mode {
+                                TimerMode::Stop | TimerMode::Pause(_) => "media-playback-start",
+                                _ => "media-playback-pause",
+                            },
+                            connect_clicked[sender] => move |_| {
+                                sender.output(MiniOutput::ToggleFlowTime).unwrap()
+                            },
+                        },
+                        gtk::Button {
+                            add_css_class: "circular",
+                            set_icon_name: "view-fullscreen-symbolic",
+                            set_tooltip_text: Some("Full window (Ctrl+M)"),
+                            connect_clicked[sender] => move |_| {
+                                sender.output(MiniOutput::Expand).unwrap()
+                            },
+                        },
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        _params: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = MiniTimer {
+            mode: TimerMode::Stop,
+            time: String::new(),
+            ring: ProgressRing::new(130),
+            visible: false,
+        };
+        let mini_ring = model.ring.widget();
+        relm4::set_global_css(
+            r#"
+            .mini .clock {
+                font-size: 22px;
+            }
+            .mini .mode {
+                font-size: 11px;
+            }
+            "#,
+        );
+        let widgets = view_output!();
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, _sender: ComponentSender<Self>) {
+        match msg {
+            MiniMsg::Update {
+                mode,
+                time,
+                fraction,
+            } => {
+                self.mode = mode;
+                self.time = time;
+                self.ring.set_fraction(fraction);
+            }
+            MiniMsg::SetVisible(visible) => self.visible = visible,
+        }
+    }
+}
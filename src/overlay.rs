@@ -0,0 +1,223 @@
+//! Fullscreen overlay shown during breaks.
+//!
+//! `Timer` keeps it up to date and decides when it is visible, the overlay only picks a
+//! message when it appears and covers the other monitors if `break_overlay_every_monitor` is set.
+//! Its windows can't be closed, closing one asks to skip the break, which strict mode refuses.
+use crate::cfg;
+use chrono::Local;
+use gtk::prelude::*;
+use relm4::*;
+
+#[derive(Debug)]
+pub struct BreakOverlay {
+    root: gtk::Window,
+    visible: bool,
+    time: String,
+    message: String,
+    /// Seconds until the break may be skipped in strict mode
+    skip_in: u32,
+    /// Whether the break wasn't postponed yet
+    can_postpone: bool,
+    /// Plain windows covering the other monitors, with their clock
+    screens: Vec<(gtk::Window, gtk::Label)>,
+}
+
+#[derive(Debug)]
+pub enum OverlayMsg {
+    Update {
+        visible: bool,
+        time: String,
+        skip_in: u32,
+        can_postpone: bool,
+    },
+}
+
+#[derive(Debug)]
+pub enum OverlayOutput {
+    Skip,
+    Postpone,
+}
+
+/// Keeps `window` from being closed, a close request skips the break instead
+fn guard(window: &gtk::Window, sender: &ComponentSender<BreakOverlay>) {
+    window.set_deletable(false);
+    let sender = sender.clone();
+    window.connect_close_request(move |_| {
+        sender.output(OverlayOutput::Skip).ok();
+        gtk::Inhibit(true)
+    });
+}
+
+impl BreakOverlay {
+    fn show(&mut self, sender: &ComponentSender<Self>) {
+        let config = cfg!();
+        self.message = match config.break_messages.len() {
+            0 => String::new(),
+            len => config.break_messages[Local::now().timestamp() as usize % len].clone(),
+        };
+        let monitors = gtk::gdk::Display::default().unwrap().monitors();
+        let monitors: Vec<gtk::gdk::Monitor> = (0..monitors.n_items())
+            .filter_map(|i| monitors.item(i)?.downcast().ok())
+            .collect();
+        match monitors.split_first() {
+            Some((first, rest)) if config.break_overlay_every_monitor => {
+                self.root.fullscreen_on_monitor(first);
+                for monitor in rest {
+                    let label = gtk::Label::new(Some(&self.time));
+                    label.add_css_class("clock");
+                    let content = gtk::Box::new(gtk::Orientation::Vertical, 0);
+                    content.add_css_class("stage-break");
+                    content.set_valign(gtk::Align::Center);
+                    content.append(&label);
+                    let window = gtk::Window::new();
+                    window.add_css_class("break-overlay");
+                    window.set_child(Some(&content));
+                    guard(&window, sender);
+                    window.fullscreen_on_monitor(monitor);
+                    window.show();
+                    self.screens.push((window, label));
+                }
+            }
+            _ => self.root.fullscreen(),
+        }
+    }
+
+    fn hide(&mut self) {
+        for (window, _) in self.screens.drain(..) {
+            window.destroy();
+        }
+    }
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for BreakOverlay {
+    type Input = OverlayMsg;
+    type Init = ();
+    type Output = OverlayOutput;
+
+    view! {
+        gtk::Window {
+            set_title: Some("Flowtime break"),
+            add_css_class: "break-overlay",
+            #[watch]
+            set_visible: model.visible,
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_valign: gtk::Align::Center,
+                set_halign: gtk::Align::Center,
+                set_spacing: 20,
+                add_css_class: "stage-break",
+
+                gtk::Label {
+                    add_css_class: "mode",
+                    set_label: "Time to step away",
+                },
+                gtk::Label {
+                    add_css_class: "clock",
+                    #[watch]
+                    set_label: &model.time,
+                },
+                gtk::Label {
+                    add_css_class: "message",
+                    set_wrap: true,
+                    set_justify: gtk::Justification::Center,
+                    #[watch]
+                    set_visible: !model.message.is_empty(),
+                    #[watch]
+                    set_label: &model.message,
+                },
+                gtk::Box {
+                    set_halign: gtk::Align::Center,
+                    set_spacing: 10,
+
+                    gtk::Button {
+                        add_css_class: "pill",
+                        #[watch]
+                        set_visible: cfg!().postpone_minutes != 0 && model.can_postpone,
+                        #[watch]
+                        set_sensitive: model.skip_in == 0,
+                        #[watch]
+                        set_label: &format!("Postpone {} min", cfg!().postpone_minutes),
+                        connect_clicked[sender] => move |_| {
+                            sender.output(OverlayOutput::Postpone).unwrap()
+                        },
+                    },
+                    gtk::Button {
+                        add_css_class: "pill",
+                        #[watch]
+                        set_sensitive: model.skip_in == 0,
+                        #[watch]
+                        set_label: &if model.skip_in == 0 {
+                            "Skip".to_string()
+                        } else {
+                            format!("Skip in {}s", model.skip_in)
+                        },
+                        connect_clicked[sender] => move |_| {
+                            sender.output(OverlayOutput::Skip).unwrap()
+                        },
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        _params: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = BreakOverlay {
+            root: root.clone(),
+            visible: false,
+            time: String::new(),
+            message: String::new(),
+            skip_in: 0,
+            can_postpone: true,
+            screens: vec![],
+        };
+        guard(root, &sender);
+        relm4::set_global_css(
+            r#"
+            .break-overlay {
+                background-color: alpha(@window_bg_color, 0.92);
+            }
+            .break-overlay .clock {
+                font-size: 96px;
+            }
+            .break-overlay .mode {
+                font-size: 24px;
+            }
+            .break-overlay .message {
+                font-size: 18px;
+            }
+            "#,
+        );
+        let widgets = view_output!();
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            OverlayMsg::Update {
+                visible,
+                time,
+                skip_in,
+                can_postpone,
+            } => {
+                for (_, label) in self.screens.iter() {
+                    label.set_label(&time);
+                }
+                self.time = time;
+                self.skip_in = skip_in;
+                self.can_postpone = can_postpone;
+                if visible && !self.visible {
+                    self.show(&sender);
+                } else if !visible && self.visible {
+                    self.hide();
+                }
+                self.visible = visible;
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Global hotkeys through the `org.freedesktop.portal.GlobalShortcuts` desktop portal.
+//!
+//! The desktop decides the actual keys, the triggers here are only suggestions. Setting
+//! `FLOWTIME_PORTAL` to another bus name talks to that instead, e.g. a mock portal.
+use crate::TimerMsg;
+use gtk::gio;
+use gtk::glib::{self, ToVariant, Variant};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+const PORTAL_BUS: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const GLOBAL_SHORTCUTS: &str = "org.freedesktop.portal.GlobalShortcuts";
+const REQUEST: &str = "org.freedesktop.portal.Request";
+
+/// Shortcut ids with their description and preferred trigger
+const HOTKEYS: [(&str, &str, &str); 3] = [
+    ("toggle", "Start or pause the timer", "CTRL+ALT+f"),
+    ("break", "Take a break", "CTRL+ALT+b"),
+    ("interruption", "Log an interruption", "CTRL+ALT+i"),
+];
+
+fn message(id: &str) -> Option<TimerMsg> {
+    match id {
+        "toggle" => Some(TimerMsg::ToggleFlowTime),
+        "break" => Some(TimerMsg::ToggleBreak),
+        "interruption" => Some(TimerMsg::LogInterruption),
+        _ => None,
+    }
+}
+
+fn portal_bus() -> String {
+    std::env::var("FLOWTIME_PORTAL").unwrap_or_else(|_| PORTAL_BUS.to_string())
+}
+
+/// Binds the hotkeys, calling `activated` with the timer message of each pressed one.
+///
+/// Failures are only printed, the app works the same without a portal.
+pub fn register(activated: impl Fn(TimerMsg) + 'static) {
+    gio::bus_get(
+        gio::BusType::Session,
+        None::<&gio::Cancellable>,
+        move |connection| match connection {
+            Ok(connection) => create_session(&connection, Rc::new(activated)),
+            Err(e) => eprintln!("flowtime: no session bus for global shortcuts: {}", e),
+        },
+    );
+}
+
+fn create_session(connection: &gio::DBusConnection, activated: Rc<dyn Fn(TimerMsg)>) {
+    request(
+        connection,
+        "CreateSession",
+        |token| {
+            let options = HashMap::from([
+                ("handle_token", token.to_variant()),
+                ("session_handle_token", "flowtime".to_variant()),
+            ]);
+            (options,).to_variant()
+        },
+        move |connection, results| match results.get("session_handle").and_then(|x| x.str()) {
+            Some(session) => bind_shortcuts(connection, session, activated),
+            None => eprintln!("flowtime: the portal didn't return a session"),
+        },
+    );
+}
+
+fn bind_shortcuts(
+    connection: &gio::DBusConnection,
+    session: &str,
+    activated: Rc<dyn Fn(TimerMsg)>,
+) {
+    let session_path = match glib::variant::ObjectPath::try_from(session.to_string()) {
+        Ok(x) => x,
+        Err(_) => {
+            eprintln!("flowtime: invalid portal session {session:?}");
+            return;
+        }
+    };
+    let shortcuts: Vec<(&str, HashMap<&str, Variant>)> = HOTKEYS
+        .iter()
+        .map(|(id, description, trigger)| {
+            (
+                *id,
+                HashMap::from([
+                    ("description", description.to_variant()),
+                    ("preferred_trigger", trigger.to_variant()),
+                ]),
+            )
+        })
+        .collect();
+    let session = session.to_string();
+    connection.signal_subscribe(
+        Some(&portal_bus()),
+        Some(GLOBAL_SHORTCUTS),
+        Some("Activated"),
+        Some(PORTAL_PATH),
+        None,
+        gio::DBusSignalFlags::NONE,
+        move |_, _, _, _, _, parameters| {
+            // (session_handle, shortcut_id, timestamp, options)
+            if parameters.child_value(0).str() != Some(session.as_str()) {
+                return;
+            }
+            if let Some(msg) = parameters.child_value(1).str().and_then(message) {
+                activated(msg);
+            }
+        },
+    );
+    request(
+        connection,
+        "BindShortcuts",
+        move |token| {
+            let options = HashMap::from([("handle_token", token.to_variant())]);
+            (session_path, shortcuts, "", options).to_variant()
+        },
+        |_, _| (),
+    );
+}
+
+/// Calls a portal method that answers through a `Request` object, `done` gets its results.
+///
+/// The request path is known before the call, so its `Response` can't be missed.
+fn request(
+    connection: &gio::DBusConnection,
+    method: &'static str,
+    parameters: impl FnOnce(&str) -> Variant,
+    done: impl FnOnce(&gio::DBusConnection, HashMap<String, Variant>) + 'static,
+) {
+    thread_local! {
+        static COUNTER: Cell<u32> = Cell::new(0);
+    }
+    let token = COUNTER.with(|x| {
+        x.set(x.get() + 1);
+        format!("flowtime{}", x.get())
+    });
+    let sender = connection
+        .unique_name()
+        .map(|x| x.trim_start_matches(':').replace('.', "_"))
+        .unwrap_or_default();
+    let path = format!("{PORTAL_PATH}/request/{sender}/{token}");
+
+    let done = RefCell::new(Some(done));
+    let subscription = Rc::new(RefCell::new(None));
+    let id = connection.signal_subscribe(
+        Some(&portal_bus()),
+        Some(REQUEST),
+        Some("Response"),
+        Some(&path),
+        None,
+        gio::DBusSignalFlags::NONE,
+        {
+            let subscription = subscription.clone();
+            move |connection, _, _, _, _, parameters| {
+                if let Some(id) = subscription.borrow_mut().take() {
+                    connection.signal_unsubscribe(id);
+                }
+                let done = match done.borrow_mut().take() {
+                    Some(x) => x,
+                    None => return,
+                };
+                match parameters.get::<(u32, HashMap<String, Variant>)>() {
+                    Some((0, results)) => done(connection, results),
+                    Some((response, _)) => eprintln!(
+                        "flowtime: global shortcuts {} was denied ({})",
+                        method, response
+                    ),
+                    None => eprintln!("flowtime: unexpected portal response {}", parameters),
+                }
+            }
+        },
+    );
+    *subscription.borrow_mut() = Some(id);
+
+    connection.call(
+        Some(&portal_bus()),
+        PORTAL_PATH,
+        GLOBAL_SHORTCUTS,
+        method,
+        Some(&parameters(&token)),
+        None,
+        gio::DBusCallFlags::NONE,
+        -1,
+        None::<&gio::Cancellable>,
+        move |result| {
+            if let Err(e) = result {
+                eprintln!("flowtime: global shortcuts {} failed: {}", method, e);
+            }
+        },
+    );
+}
@@ -0,0 +1,215 @@
+//! Whether the user is at the computer, from logind's idle and lock hints, its sleep signal
+//! and the screensaver.
+//!
+//! logind is on the system bus, so setting `DBUS_SYSTEM_BUS_ADDRESS` points it at a mock
+//! instead, e.g. python-dbusmock's logind template, with `XDG_SESSION_ID` naming its session.
+use chrono::{DateTime, Local, TimeZone};
+use gtk::gio;
+use gtk::glib::thread_guard::ThreadGuard;
+use gtk::glib::{ToVariant, Variant, VariantTy};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+const LOGIND: &str = "org.freedesktop.login1";
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+const MANAGER: &str = "org.freedesktop.login1.Manager";
+const SESSION: &str = "org.freedesktop.login1.Session";
+const PROPERTIES: &str = "org.freedesktop.DBus.Properties";
+const SCREENSAVERS: [&str; 2] = ["org.freedesktop.ScreenSaver", "org.gnome.ScreenSaver"];
+
+#[derive(Debug)]
+pub enum Presence {
+    /// Nobody touched the computer since then
+    Idle(DateTime<Local>),
+    Active,
+    /// The session was locked, unlocking needs nothing as whatever runs goes on
+    Locked,
+    /// The system is about to suspend, since when, it waits until the delay is dropped
+    Sleeping(DateTime<Local>, SleepDelay),
+    Resumed,
+}
+
+/// logind's delay lock on suspending, released when this is dropped. It only goes with
+/// `Sleeping` so the receiver decides when it is done, and has to be dropped on the main thread.
+pub struct SleepDelay(Option<ThreadGuard<gio::UnixFDList>>);
+
+impl std::fmt::Debug for SleepDelay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(if self.0.is_some() {
+            "SleepDelay"
+        } else {
+            "SleepDelay(none)"
+        })
+    }
+}
+
+/// Calls `changed` whenever the user goes idle, comes back, locks the session or it sleeps.
+///
+/// Failures are only printed, without them the timer just never pauses by itself.
+pub fn watch(changed: impl Fn(Presence) + 'static) {
+    let changed: Rc<dyn Fn(Presence)> = Rc::new(changed);
+    let logind = changed.clone();
+    gio::bus_get(
+        gio::BusType::System,
+        None::<&gio::Cancellable>,
+        move |connection| match connection {
+            Ok(connection) => {
+                watch_sleep(&connection, logind.clone());
+                watch_session(&connection, logind);
+            }
+            Err(e) => eprintln!("flowtime: no system bus to watch logind: {}", e),
+        },
+    );
+    gio::bus_get(
+        gio::BusType::Session,
+        None::<&gio::Cancellable>,
+        move |connection| match connection {
+            Ok(connection) => watch_screensaver(&connection, changed),
+            Err(e) => eprintln!("flowtime: no session bus for idle detection: {}", e),
+        },
+    );
+}
+
+/// Asks logind to hold off suspending until the lock in `inhibitor` is dropped
+fn inhibit(connection: &gio::DBusConnection, inhibitor: Rc<RefCell<Option<gio::UnixFDList>>>) {
+    connection.call_with_unix_fd_list(
+        Some(LOGIND),
+        LOGIND_PATH,
+        MANAGER,
+        "Inhibit",
+        Some(&("sleep", "Flowtime", "Pausing the timer", "delay").to_variant()),
+        Some(VariantTy::new("(h)").unwrap()),
+        gio::DBusCallFlags::NONE,
+        -1,
+        None::<&gio::UnixFDList>,
+        None::<&gio::Cancellable>,
+        move |result| match result {
+            // the lock is the file descriptor, it lasts until the list is dropped
+            Ok((_, fds)) => *inhibitor.borrow_mut() = Some(fds),
+            Err(e) => eprintln!("flowtime: failed to delay suspending: {}", e),
+        },
+    );
+}
+
+/// Without a delay lock the process may be frozen before it hears of the suspend, so one is
+/// held while awake and handed over with `Sleeping`
+fn watch_sleep(connection: &gio::DBusConnection, changed: Rc<dyn Fn(Presence)>) {
+    let inhibitor = Rc::new(RefCell::new(None));
+    inhibit(connection, inhibitor.clone());
+    connection.signal_subscribe(
+        Some(LOGIND),
+        Some(MANAGER),
+        Some("PrepareForSleep"),
+        Some(LOGIND_PATH),
+        None,
+        gio::DBusSignalFlags::NONE,
+        move |connection, _, _, _, _, parameters| match parameters.child_value(0).get::<bool>() {
+            Some(true) => {
+                let delay = SleepDelay(inhibitor.borrow_mut().take().map(ThreadGuard::new));
+                changed(Presence::Sleeping(Local::now(), delay));
+            }
+            Some(false) => {
+                changed(Presence::Resumed);
+                inhibit(connection, inhibitor.clone());
+            }
+            None => (),
+        },
+    );
+}
+
+/// Converts logind's microseconds since the epoch, 0 meaning unknown
+fn from_micros(micros: u64) -> DateTime<Local> {
+    match micros {
+        0 => Local::now(),
+        x => Local
+            .timestamp_opt((x / 1_000_000) as i64, 0)
+            .single()
+            .unwrap_or_else(Local::now),
+    }
+}
+
+fn watch_session(connection: &gio::DBusConnection, changed: Rc<dyn Fn(Presence)>) {
+    let id = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "auto".to_string());
+    let subscriber = connection.clone();
+    connection.call(
+        Some(LOGIND),
+        LOGIND_PATH,
+        MANAGER,
+        "GetSession",
+        Some(&(id,).to_variant()),
+        Some(VariantTy::new("(o)").unwrap()),
+        gio::DBusCallFlags::NONE,
+        -1,
+        None::<&gio::Cancellable>,
+        move |result| {
+            let path = match result {
+                Ok(reply) => reply.child_value(0).str().unwrap_or_default().to_string(),
+                Err(e) => {
+                    eprintln!("flowtime: no logind session to watch: {}", e);
+                    return;
+                }
+            };
+            let locked = changed.clone();
+            subscriber.signal_subscribe(
+                Some(LOGIND),
+                Some(SESSION),
+                Some("Lock"),
+                Some(&path),
+                None,
+                gio::DBusSignalFlags::NONE,
+                move |_, _, _, _, _, _| locked(Presence::Locked),
+            );
+            subscriber.signal_subscribe(
+                Some(LOGIND),
+                Some(PROPERTIES),
+                Some("PropertiesChanged"),
+                Some(&path),
+                Some(SESSION),
+                gio::DBusSignalFlags::NONE,
+                move |_, _, _, _, _, parameters| {
+                    // (interface, changed, invalidated)
+                    let changes = parameters.child_value(1);
+                    let properties = match changes.get::<HashMap<String, Variant>>() {
+                        Some(x) => x,
+                        None => return,
+                    };
+                    match properties.get("IdleHint").and_then(|x| x.get::<bool>()) {
+                        Some(true) => {
+                            let since = properties
+                                .get("IdleSinceHint")
+                                .and_then(|x| x.get::<u64>())
+                                .unwrap_or(0);
+                            changed(Presence::Idle(from_micros(since)))
+                        }
+                        Some(false) => changed(Presence::Active),
+                        None => (),
+                    }
+                    // lock screens set the hint, `Lock` is only sent for `loginctl lock-session`
+                    if let Some(true) = properties.get("LockedHint").and_then(|x| x.get::<bool>()) {
+                        changed(Presence::Locked)
+                    }
+                },
+            );
+        },
+    );
+}
+
+fn watch_screensaver(connection: &gio::DBusConnection, changed: Rc<dyn Fn(Presence)>) {
+    for interface in SCREENSAVERS {
+        let changed = changed.clone();
+        connection.signal_subscribe(
+            None,
+            Some(interface),
+            Some("ActiveChanged"),
+            None,
+            None,
+            gio::DBusSignalFlags::NONE,
+            move |_, _, _, _, _, parameters| match parameters.child_value(0).get::<bool>() {
+                Some(true) => changed(Presence::Idle(Local::now())),
+                Some(false) => changed(Presence::Active),
+                None => (),
+            },
+        );
+    }
+}
@@ -0,0 +1,106 @@
+//! Named settings profiles.
+//!
+//! The default profile is the plain `flowtime` config, every other profile is a
+//! `profile-<name>` config next to it, and the active profile name is kept in `active_profile`.
+use crate::state;
+use anyhow::{bail, Context};
+use std::path::PathBuf;
+
+pub const DEFAULT: &str = "Default";
+
+fn active_path() -> PathBuf {
+    state::path("flowtime").with_file_name("active_profile")
+}
+
+/// The confy config name holding the profile
+pub fn config_name(profile: &str) -> String {
+    if profile == DEFAULT {
+        "flowtime".to_string()
+    } else {
+        format!("profile-{}", profile)
+    }
+}
+
+pub fn active() -> String {
+    std::fs::read_to_string(active_path())
+        .map(|x| x.trim().to_string())
+        .ok()
+        .filter(|x| !x.is_empty() && exists(x))
+        .unwrap_or_else(|| DEFAULT.to_string())
+}
+
+pub fn exists(profile: &str) -> bool {
+    profile == DEFAULT || state::path(&config_name(profile)).exists()
+}
+
+/// Every profile, the default one first
+pub fn list() -> Vec<String> {
+    let mut profiles: Vec<String> = state::path("flowtime")
+        .parent()
+        .and_then(|x| std::fs::read_dir(x).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|x| x.ok()?.file_name().into_string().ok())
+        .filter_map(|x| {
+            Some(
+                x.strip_prefix("profile-")?
+                    .strip_suffix(".toml")?
+                    .to_string(),
+            )
+        })
+        .collect();
+    profiles.sort();
+    profiles.insert(0, DEFAULT.to_string());
+    profiles
+}
+
+/// Makes the profile active, a new profile starts as a copy of the active one
+pub fn switch(profile: &str) -> anyhow::Result<()> {
+    if profile.is_empty() || profile.contains(['/', '\\']) {
+        bail!("invalid profile name {profile:?}");
+    }
+    if !exists(profile) {
+        confy::store(
+            "flowtime",
+            Some(config_name(profile).as_str()),
+            state::config(),
+        )?;
+    }
+    std::fs::write(active_path(), profile)
+        .with_context(|| format!("failed to write {}", active_path().display()))?;
+    state::reload_config();
+    Ok(())
+}
+
+/// `flowtime profile [list | use NAME | remove NAME]`
+pub fn command(args: &[String]) -> anyhow::Result<()> {
+    match args.first().map(String::as_str) {
+        None | Some("list") => {
+            let active = active();
+            for profile in list() {
+                let marker = if profile == active { "*" } else { " " };
+                println!("{} {}", marker, profile);
+            }
+        }
+        Some("use") => {
+            let profile = args.get(1).context("usage: flowtime profile use NAME")?;
+            switch(profile)?;
+            println!("switched to {}", profile);
+        }
+        Some("remove") => {
+            let profile = args.get(1).context("usage: flowtime profile remove NAME")?;
+            if profile == DEFAULT {
+                bail!("the default profile can't be removed");
+            }
+            if !exists(profile) {
+                bail!("no profile named {profile:?}");
+            }
+            if &active() == profile {
+                switch(DEFAULT)?;
+            }
+            std::fs::remove_file(state::path(&config_name(profile)))?;
+        }
+        _ => bail!("usage: flowtime profile [list | use NAME | remove NAME]"),
+    }
+    Ok(())
+}
@@ -0,0 +1,82 @@
+//! Circular progress indicator with room for other widgets in its middle.
+use gtk::prelude::*;
+use std::cell::Cell;
+use std::f64::consts::PI;
+use std::rc::Rc;
+
+/// A ring filled clockwise from the top, drawn in the CSS `color` of the `.ring` class.
+///
+/// Widgets shown inside of it are added with `add_overlay` on `widget()`.
+#[derive(Debug, Clone)]
+pub struct ProgressRing {
+    overlay: gtk::Overlay,
+    area: gtk::DrawingArea,
+    fraction: Rc<Cell<f64>>,
+}
+
+impl ProgressRing {
+    pub fn new(size: i32) -> ProgressRing {
+        let fraction = Rc::new(Cell::new(0.0));
+        let area = gtk::DrawingArea::new();
+        area.add_css_class("ring");
+        area.set_content_width(size);
+        area.set_content_height(size);
+        let drawn = fraction.clone();
+        area.set_draw_func(move |area, cr, width, height| {
+            let color = area.style_context().color();
+            let (width, height) = (width as f64, height as f64);
+            let line = width.min(height) / 25.0;
+            let radius = width.min(height) / 2.0 - line;
+            cr.set_line_width(line);
+            cr.set_line_cap(gtk::cairo::LineCap::Round);
+
+            cr.set_source_rgba(
+                color.red() as f64,
+                color.green() as f64,
+                color.blue() as f64,
+                color.alpha() as f64 * 0.15,
+            );
+            cr.arc(width / 2.0, height / 2.0, radius, 0.0, 2.0 * PI);
+            cr.stroke().unwrap();
+
+            if drawn.get() > 0.0 {
+                cr.set_source_rgba(
+                    color.red() as f64,
+                    color.green() as f64,
+                    color.blue() as f64,
+                    color.alpha() as f64,
+                );
+                let start = -PI / 2.0;
+                cr.arc(
+                    width / 2.0,
+                    height / 2.0,
+                    radius,
+                    start,
+                    start + 2.0 * PI * drawn.get(),
+                );
+                cr.stroke().unwrap();
+            }
+        });
+        let overlay = gtk::Overlay::new();
+        overlay.set_child(Some(&area));
+        overlay.set_halign(gtk::Align::Center);
+        ProgressRing {
+            overlay,
+            area,
+            fraction,
+        }
+    }
+
+    pub fn widget(&self) -> &gtk::Overlay {
+        &self.overlay
+    }
+
+    /// Fills the ring up to `fraction`, clamped to 0..=1
+    pub fn set_fraction(&self, fraction: f64) {
+        let fraction = fraction.clamp(0.0, 1.0);
+        if fraction != self.fraction.get() {
+            self.fraction.set(fraction);
+            self.area.queue_draw();
+        }
+    }
+}
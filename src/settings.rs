@@ -0,0 +1,889 @@
+use crate::{cfg, config, shortcuts, state, storage, theme, webhooks, Config};
+use gtk::prelude::*;
+use relm4::adw::prelude::*;
+use relm4::*;
+use std::path::PathBuf;
+
+const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+/// Every option that can be reset to its default on its own
+#[derive(Debug, Clone, Copy)]
+pub enum Setting {
+    Restart,
+    ResetSave,
+    RunInBackground,
+    BreakRatio,
+    BreakOverlay,
+    BreakOverlayEveryMonitor,
+    BreakMessages,
+    Webhooks,
+    ApiPort,
+    StrictBreak,
+    Postpone,
+    MinFocus,
+    MaxFlow,
+    Idle,
+    BreakOnLock,
+    Sounds,
+    Sound,
+    Notifications,
+    DailyGoal,
+    WeeklyGoal,
+    WeekdayGoal(u32),
+    StreakRequiresGoal,
+    Theme,
+    ColorScheme,
+    Shortcut(shortcuts::Action),
+    GlobalShortcuts,
+    Tray,
+}
+
+impl Setting {
+    fn set_bool(self, config: &mut Config, value: bool) {
+        match self {
+            Setting::Restart => config.restart = value,
+            Setting::ResetSave => config.reset_save = value,
+            Setting::RunInBackground => config.run_in_background = value,
+            Setting::BreakOnLock => config.break_on_lock = value,
+            Setting::BreakOverlay => config.break_overlay = value,
+            Setting::BreakOverlayEveryMonitor => config.break_overlay_every_monitor = value,
+            Setting::Sounds => config.sounds = value,
+            Setting::Notifications => config.notifications = value,
+            Setting::StreakRequiresGoal => config.streak_requires_goal = value,
+            Setting::GlobalShortcuts => config.global_shortcuts = value,
+            Setting::Tray => config.tray = value,
+            _ => unreachable!("{:?} is not a switch", self),
+        }
+    }
+
+    fn set_number(self, config: &mut Config, value: u32) {
+        match self {
+            Setting::BreakRatio => config.break_ratio = value,
+            Setting::StrictBreak => config.strict_break_seconds = value,
+            Setting::Postpone => config.postpone_minutes = value,
+            Setting::MinFocus => config.min_focus_minutes = value,
+            Setting::MaxFlow => config.max_flow_minutes = value,
+            Setting::Idle => config.idle_minutes = value,
+            Setting::ApiPort => config.api_port = value.min(u16::MAX as u32) as u16,
+            Setting::DailyGoal => config.daily_goal_minutes = value,
+            Setting::WeeklyGoal => config.weekly_goal_minutes = value,
+            Setting::WeekdayGoal(weekday) => {
+                config.weekday_goal_minutes.retain(|(x, _)| *x != weekday);
+                config.weekday_goal_minutes.push((weekday, value));
+                config.weekday_goal_minutes.sort();
+            }
+            _ => unreachable!("{:?} is not a number", self),
+        }
+    }
+
+    fn reset(self, config: &mut Config) {
+        let default = Config::default();
+        match self {
+            Setting::Restart => config.restart = default.restart,
+            Setting::ResetSave => config.reset_save = default.reset_save,
+            Setting::RunInBackground => config.run_in_background = default.run_in_background,
+            Setting::BreakRatio => config.break_ratio = default.break_ratio,
+            Setting::BreakOverlay => config.break_overlay = default.break_overlay,
+            Setting::BreakOverlayEveryMonitor => {
+                config.break_overlay_every_monitor = default.break_overlay_every_monitor
+            }
+            Setting::BreakMessages => config.break_messages = default.break_messages,
+            Setting::Webhooks => config.webhooks = default.webhooks,
+            Setting::ApiPort => config.api_port = default.api_port,
+            Setting::StrictBreak => config.strict_break_seconds = default.strict_break_seconds,
+            Setting::Postpone => config.postpone_minutes = default.postpone_minutes,
+            Setting::MinFocus => config.min_focus_minutes = default.min_focus_minutes,
+            Setting::MaxFlow => config.max_flow_minutes = default.max_flow_minutes,
+            Setting::Idle => config.idle_minutes = default.idle_minutes,
+            Setting::BreakOnLock => config.break_on_lock = default.break_on_lock,
+            Setting::Sounds => config.sounds = default.sounds,
+            Setting::Sound => config.sound = default.sound,
+            Setting::Notifications => config.notifications = default.notifications,
+            Setting::DailyGoal => config.daily_goal_minutes = default.daily_goal_minutes,
+            Setting::WeeklyGoal => config.weekly_goal_minutes = default.weekly_goal_minutes,
+            Setting::WeekdayGoal(weekday) => {
+                config.weekday_goal_minutes.retain(|(x, _)| *x != weekday);
+                config.weekday_goal_minutes.extend(
+                    default
+                        .weekday_goal_minutes
+                        .iter()
+                        .filter(|(x, _)| *x == weekday),
+                );
+                config.weekday_goal_minutes.sort();
+            }
+            Setting::StreakRequiresGoal => {
+                config.streak_requires_goal = default.streak_requires_goal
+            }
+            Setting::Theme => config.theme = default.theme,
+            Setting::ColorScheme => config.color_scheme = default.color_scheme,
+            Setting::GlobalShortcuts => config.global_shortcuts = default.global_shortcuts,
+            Setting::Tray => config.tray = default.tray,
+            Setting::Shortcut(action) => {
+                *action.binding_mut(&mut config.shortcuts) =
+                    action.binding(&default.shortcuts).to_string()
+            }
+        }
+    }
+}
+
+/// Goal of a weekday, falling back to the daily goal without an override
+fn weekday_goal(config: &Config, weekday: u32) -> u32 {
+    config
+        .weekday_goal_minutes
+        .iter()
+        .find(|(x, _)| *x == weekday)
+        .map(|(_, minutes)| *minutes)
+        .unwrap_or(config.daily_goal_minutes)
+}
+
+/// Break messages or webhook URLs typed into the settings, one per line
+fn lines(buffer: &gtk::TextBuffer) -> Vec<String> {
+    buffer
+        .text(&buffer.start_iter(), &buffer.end_iter(), false)
+        .lines()
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[derive(Debug)]
+pub struct SettingsModel {
+    config: Config,
+    /// Why the last edit was not saved, or why the config file is unusable
+    error: Option<String>,
+    weekday_spins: Vec<(gtk::SpinButton, gtk::glib::SignalHandlerId)>,
+    messages: (gtk::TextBuffer, gtk::glib::SignalHandlerId),
+    /// Applied when the view loses focus, half typed URLs would not validate
+    webhooks: (gtk::TextBuffer, gtk::TextView),
+    /// How the last test of the webhooks went
+    webhook_status: Option<String>,
+    shortcut_entries: Vec<(shortcuts::Action, gtk::Entry, gtk::glib::SignalHandlerId)>,
+    // native dialogs have to be kept alive while they are shown
+    sound_dialog: Option<gtk::FileChooserNative>,
+}
+
+#[derive(Debug)]
+pub enum SettingsMsg {
+    SetBool(Setting, bool),
+    SetNumber(Setting, u32),
+    SetSound(PathBuf),
+    SetStorage(storage::Backend),
+    SetTheme(theme::Theme),
+    SetColorScheme(theme::ColorScheme),
+    SetMessages(Vec<String>),
+    SetWebhooks(Vec<String>),
+    SetShortcut(shortcuts::Action, String),
+    Reset(Setting),
+    ChooseSound,
+    TestWebhooks,
+    WebhooksTested(String),
+    /// An entry lost focus, rejected text in it goes back to the value in effect
+    Revert,
+    /// The config changed on disk or the profile was switched
+    Reload,
+}
+
+#[derive(Debug)]
+pub enum SettingsOutput {
+    /// The config was changed and saved from here
+    Saved,
+}
+
+impl SettingsModel {
+    /// Saves the edit if it is valid. A rejected value stays out of `config`, the view puts
+    /// every widget back to it, except entries that are still being typed into.
+    fn edit(&mut self, sender: &ComponentSender<Self>, f: impl FnOnce(&mut Config)) {
+        let mut config = self.config.clone();
+        f(&mut config);
+        let errors = config::validate(&config);
+        if !errors.is_empty() {
+            self.error = Some(errors.join("\n"));
+            return;
+        }
+        match state::save_config(config.clone()) {
+            Ok(()) => {
+                self.error = None;
+                self.config = config;
+                // saving doesn't go through the file watch, so the app has to be told
+                sender.output(SettingsOutput::Saved).unwrap();
+            }
+            Err(e) => self.error = Some(format!("{:#}", e)),
+        }
+    }
+}
+
+fn reset_button(sender: &ComponentSender<SettingsModel>, setting: Setting) -> gtk::Button {
+    let button = gtk::Button::from_icon_name("edit-undo-symbolic");
+    button.add_css_class("flat");
+    button.set_valign(gtk::Align::Center);
+    button.set_tooltip_text(Some("Reset to default"));
+    let sender = sender.clone();
+    button.connect_clicked(move |_| sender.input(SettingsMsg::Reset(setting)));
+    button
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for SettingsModel {
+    type Input = SettingsMsg;
+    type Init = ();
+    type Output = SettingsOutput;
+
+    view! {
+        adw::PreferencesPage {
+            set_width_request: 460,
+            set_height_request: 480,
+
+            add = &adw::PreferencesGroup {
+                gtk::Label {
+                    add_css_class: "error",
+                    set_wrap: true,
+                    #[watch]
+                    set_visible: model.error.is_some(),
+                    #[watch]
+                    set_label: model.error.as_deref().unwrap_or_default(),
+                },
+            },
+            add = &adw::PreferencesGroup {
+                set_title: "Timer",
+                adw::ActionRow {
+                    set_title: "Auto restart",
+                    set_subtitle: "Start a Flowtime session after the break has ended",
+                    set_activatable_widget: Some(&restart),
+                    #[name = "restart"]
+                    add_suffix = &gtk::Switch {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(restart_handler)]
+                        set_active: model.config.restart,
+                        connect_state_notify[sender] => move |switch| {
+                            sender.input(SettingsMsg::SetBool(Setting::Restart, switch.is_active()));
+                        } @restart_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::Restart),
+                },
+                adw::ActionRow {
+                    set_title: "Save on reset",
+                    set_subtitle: "Save the current work time to statistics when reset is pressed",
+                    set_activatable_widget: Some(&reset_save),
+                    #[name = "reset_save"]
+                    add_suffix = &gtk::Switch {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(reset_save_handler)]
+                        set_active: model.config.reset_save,
+                        connect_state_notify[sender] => move |switch| {
+                            sender.input(SettingsMsg::SetBool(Setting::ResetSave, switch.is_active()));
+                        } @reset_save_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::ResetSave),
+                },
+                adw::ActionRow {
+                    set_title: "Keep running in background",
+                    set_subtitle: "Closing the window only hides it, the timer goes on and the tray icon brings it back",
+                    set_activatable_widget: Some(&run_in_background),
+                    #[name = "run_in_background"]
+                    add_suffix = &gtk::Switch {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(run_in_background_handler)]
+                        set_active: model.config.run_in_background,
+                        connect_state_notify[sender] => move |switch| {
+                            sender.input(SettingsMsg::SetBool(Setting::RunInBackground, switch.is_active()));
+                        } @run_in_background_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::RunInBackground),
+                },
+                adw::ActionRow {
+                    set_title: "Pause when idle",
+                    set_subtitle: "Minutes away from the computer before work pauses itself, 0 disables it",
+                    add_suffix = &gtk::SpinButton::with_range(0.0, 120.0, 1.0) {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(idle_handler)]
+                        set_value: model.config.idle_minutes as f64,
+                        connect_value_changed[sender] => move |spin| {
+                            sender.input(SettingsMsg::SetNumber(Setting::Idle, spin.value() as u32));
+                        } @idle_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::Idle),
+                },
+                adw::ActionRow {
+                    set_title: "Break on lock",
+                    set_subtitle: "Locking the screen during work starts the break, suspending always pauses",
+                    set_activatable_widget: Some(&break_on_lock),
+                    #[name = "break_on_lock"]
+                    add_suffix = &gtk::Switch {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(break_on_lock_handler)]
+                        set_active: model.config.break_on_lock,
+                        connect_state_notify[sender] => move |switch| {
+                            sender.input(SettingsMsg::SetBool(Setting::BreakOnLock, switch.is_active()));
+                        } @break_on_lock_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::BreakOnLock),
+                },
+            },
+            add = &adw::PreferencesGroup {
+                set_title: "Breaks",
+                adw::ActionRow {
+                    set_title: "Break ratio",
+                    set_subtitle: "Breaks last one nth of the work before them",
+                    add_suffix = &gtk::SpinButton::with_range(1.0, 20.0, 1.0) {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(break_ratio_handler)]
+                        set_value: model.config.break_ratio as f64,
+                        connect_value_changed[sender] => move |spin| {
+                            sender.input(SettingsMsg::SetNumber(Setting::BreakRatio, spin.value() as u32));
+                        } @break_ratio_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::BreakRatio),
+                },
+                adw::ActionRow {
+                    set_title: "Break overlay",
+                    set_subtitle: "Cover the screen during breaks",
+                    set_activatable_widget: Some(&break_overlay),
+                    #[name = "break_overlay"]
+                    add_suffix = &gtk::Switch {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(break_overlay_handler)]
+                        set_active: model.config.break_overlay,
+                        connect_state_notify[sender] => move |switch| {
+                            sender.input(SettingsMsg::SetBool(Setting::BreakOverlay, switch.is_active()));
+                        } @break_overlay_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::BreakOverlay),
+                },
+                adw::ActionRow {
+                    set_title: "Every monitor",
+                    set_subtitle: "Cover all monitors instead of one",
+                    set_activatable_widget: Some(&every_monitor),
+                    #[watch]
+                    set_sensitive: model.config.break_overlay,
+                    #[name = "every_monitor"]
+                    add_suffix = &gtk::Switch {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(every_monitor_handler)]
+                        set_active: model.config.break_overlay_every_monitor,
+                        connect_state_notify[sender] => move |switch| {
+                            sender.input(SettingsMsg::SetBool(Setting::BreakOverlayEveryMonitor, switch.is_active()));
+                        } @every_monitor_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::BreakOverlayEveryMonitor),
+                },
+                adw::ActionRow {
+                    set_title: "Strict mode",
+                    set_subtitle: "Seconds into a break before it can be skipped, 0 disables it",
+                    #[watch]
+                    set_sensitive: model.config.break_overlay,
+                    add_suffix = &gtk::SpinButton::with_range(0.0, 3600.0, 10.0) {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(strict_break_handler)]
+                        set_value: model.config.strict_break_seconds as f64,
+                        connect_value_changed[sender] => move |spin| {
+                            sender.input(SettingsMsg::SetNumber(Setting::StrictBreak, spin.value() as u32));
+                        } @strict_break_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::StrictBreak),
+                },
+                adw::ActionRow {
+                    set_title: "Postpone",
+                    set_subtitle: "Minutes a break can be postponed by, 0 disables it",
+                    #[watch]
+                    set_sensitive: model.config.break_overlay,
+                    add_suffix = &gtk::SpinButton::with_range(0.0, 60.0, 1.0) {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(postpone_handler)]
+                        set_value: model.config.postpone_minutes as f64,
+                        connect_value_changed[sender] => move |spin| {
+                            sender.input(SettingsMsg::SetNumber(Setting::Postpone, spin.value() as u32));
+                        } @postpone_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::Postpone),
+                },
+                adw::ExpanderRow {
+                    set_title: "Break messages",
+                    set_subtitle: "One per line, the overlay shows a random one",
+                    add_action: &reset_button(&sender, Setting::BreakMessages),
+                    add_row = &gtk::TextView::with_buffer(&model.messages.0) {
+                        set_wrap_mode: gtk::WrapMode::WordChar,
+                        set_top_margin: 6,
+                        set_bottom_margin: 6,
+                        set_left_margin: 12,
+                        set_right_margin: 12,
+                    },
+                },
+                adw::ActionRow {
+                    set_title: "Long session nudge",
+                    set_subtitle: "Minutes of continuous work before suggesting a break, 0 disables it",
+                    add_suffix = &gtk::SpinButton::with_range(0.0, 480.0, 5.0) {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(max_flow_handler)]
+                        set_value: model.config.max_flow_minutes as f64,
+                        connect_value_changed[sender] => move |spin| {
+                            sender.input(SettingsMsg::SetNumber(Setting::MaxFlow, spin.value() as u32));
+                        } @max_flow_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::MaxFlow),
+                },
+                adw::ActionRow {
+                    set_title: "Minimum focus",
+                    set_subtitle: "Sessions shorter than this many minutes count as fragmented, 0 disables it",
+                    add_suffix = &gtk::SpinButton::with_range(0.0, 240.0, 1.0) {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(min_focus_handler)]
+                        set_value: model.config.min_focus_minutes as f64,
+                        connect_value_changed[sender] => move |spin| {
+                            sender.input(SettingsMsg::SetNumber(Setting::MinFocus, spin.value() as u32));
+                        } @min_focus_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::MinFocus),
+                },
+            },
+            add = &adw::PreferencesGroup {
+                set_title: "Sounds",
+                adw::ActionRow {
+                    set_title: "Play sounds",
+                    set_subtitle: "When a break ends or a nudge is sent",
+                    set_activatable_widget: Some(&sounds),
+                    #[name = "sounds"]
+                    add_suffix = &gtk::Switch {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(sounds_handler)]
+                        set_active: model.config.sounds,
+                        connect_state_notify[sender] => move |switch| {
+                            sender.input(SettingsMsg::SetBool(Setting::Sounds, switch.is_active()));
+                        } @sounds_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::Sounds),
+                },
+                adw::ActionRow {
+                    set_title: "Sound file",
+                    #[watch]
+                    set_subtitle: &model.config.sound().display().to_string(),
+                    add_suffix = &gtk::Button {
+                        set_label: "Choose",
+                        set_valign: gtk::Align::Center,
+                        connect_clicked => SettingsMsg::ChooseSound,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::Sound),
+                },
+            },
+            add = &adw::PreferencesGroup {
+                set_title: "Notifications",
+                adw::ActionRow {
+                    set_title: "Desktop notifications",
+                    set_subtitle: "For nudges, goals and other events",
+                    set_activatable_widget: Some(&notifications),
+                    #[name = "notifications"]
+                    add_suffix = &gtk::Switch {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(notifications_handler)]
+                        set_active: model.config.notifications,
+                        connect_state_notify[sender] => move |switch| {
+                            sender.input(SettingsMsg::SetBool(Setting::Notifications, switch.is_active()));
+                        } @notifications_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::Notifications),
+                },
+            },
+            add = &adw::PreferencesGroup {
+                set_title: "Goals",
+                adw::ActionRow {
+                    set_title: "Daily goal",
+                    set_subtitle: "Focus minutes per day, 0 disables it",
+                    add_suffix = &gtk::SpinButton::with_range(0.0, 1440.0, 15.0) {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(daily_goal_handler)]
+                        set_value: model.config.daily_goal_minutes as f64,
+                        connect_value_changed[sender] => move |spin| {
+                            sender.input(SettingsMsg::SetNumber(Setting::DailyGoal, spin.value() as u32));
+                        } @daily_goal_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::DailyGoal),
+                },
+                #[name = "weekdays"]
+                adw::ExpanderRow {
+                    set_title: "Weekday goals",
+                    set_subtitle: "Override the daily goal for single weekdays",
+                },
+                adw::ActionRow {
+                    set_title: "Weekly goal",
+                    set_subtitle: "Focus minutes per week, 0 disables it",
+                    add_suffix = &gtk::SpinButton::with_range(0.0, 10080.0, 30.0) {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(weekly_goal_handler)]
+                        set_value: model.config.weekly_goal_minutes as f64,
+                        connect_value_changed[sender] => move |spin| {
+                            sender.input(SettingsMsg::SetNumber(Setting::WeeklyGoal, spin.value() as u32));
+                        } @weekly_goal_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::WeeklyGoal),
+                },
+                adw::ActionRow {
+                    set_title: "Strict streaks",
+                    set_subtitle: "Only count days meeting the daily goal towards streaks",
+                    set_activatable_widget: Some(&streak),
+                    #[name = "streak"]
+                    add_suffix = &gtk::Switch {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(streak_handler)]
+                        set_active: model.config.streak_requires_goal,
+                        connect_state_notify[sender] => move |switch| {
+                            sender.input(SettingsMsg::SetBool(Setting::StreakRequiresGoal, switch.is_active()));
+                        } @streak_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::StreakRequiresGoal),
+                },
+            },
+            add = &adw::PreferencesGroup {
+                set_title: "Appearance",
+                adw::ComboRow {
+                    set_title: "Theme",
+                    set_subtitle: "Colors of the work, break and pause stages",
+                    set_model: Some(&gtk::StringList::new(
+                        &theme::Theme::ALL.map(theme::Theme::name)
+                    )),
+                    #[watch]
+                    #[block_signal(theme_handler)]
+                    set_selected: theme::Theme::ALL
+                        .iter()
+                        .position(|x| *x == model.config.theme)
+                        .unwrap_or(0) as u32,
+                    connect_selected_notify[sender] => move |row| {
+                        if let Some(value) = theme::Theme::ALL.get(row.selected() as usize) {
+                            sender.input(SettingsMsg::SetTheme(*value));
+                        }
+                    } @theme_handler,
+                    add_suffix: &reset_button(&sender, Setting::Theme),
+                },
+                adw::ComboRow {
+                    set_title: "Style",
+                    set_subtitle: "Light, dark or following the system",
+                    set_model: Some(&gtk::StringList::new(
+                        &theme::ColorScheme::ALL.map(theme::ColorScheme::name)
+                    )),
+                    #[watch]
+                    #[block_signal(color_scheme_handler)]
+                    set_selected: theme::ColorScheme::ALL
+                        .iter()
+                        .position(|x| *x == model.config.color_scheme)
+                        .unwrap_or(0) as u32,
+                    connect_selected_notify[sender] => move |row| {
+                        if let Some(value) = theme::ColorScheme::ALL.get(row.selected() as usize) {
+                            sender.input(SettingsMsg::SetColorScheme(*value));
+                        }
+                    } @color_scheme_handler,
+                    add_suffix: &reset_button(&sender, Setting::ColorScheme),
+                },
+                adw::ActionRow {
+                    set_title: "Custom style",
+                    set_subtitle: &format!(
+                        "CSS in {} is loaded on top of the theme",
+                        theme::user_css_path().display()
+                    ),
+                },
+                adw::ActionRow {
+                    set_title: "Tray icon",
+                    set_subtitle: "Show the stage and time in the system tray, after a restart",
+                    set_activatable_widget: Some(&tray),
+                    #[name = "tray"]
+                    add_suffix = &gtk::Switch {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(tray_handler)]
+                        set_active: model.config.tray,
+                        connect_state_notify[sender] => move |switch| {
+                            sender.input(SettingsMsg::SetBool(Setting::Tray, switch.is_active()));
+                        } @tray_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::Tray),
+                },
+            },
+            #[name = "shortcuts_group"]
+            add = &adw::PreferencesGroup {
+                set_title: "Keyboard shortcuts",
+                set_description: Some("GTK accelerators like <Control>m, empty to disable"),
+                adw::ActionRow {
+                    set_title: "Global shortcuts",
+                    set_subtitle: "Toggle, break and log interruptions from other apps through the desktop portal, after a restart",
+                    set_activatable_widget: Some(&global_shortcuts),
+                    #[name = "global_shortcuts"]
+                    add_suffix = &gtk::Switch {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(global_shortcuts_handler)]
+                        set_active: model.config.global_shortcuts,
+                        connect_state_notify[sender] => move |switch| {
+                            sender.input(SettingsMsg::SetBool(Setting::GlobalShortcuts, switch.is_active()));
+                        } @global_shortcuts_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::GlobalShortcuts),
+                },
+            },
+            add = &adw::PreferencesGroup {
+                set_title: "Webhooks",
+                adw::ExpanderRow {
+                    set_title: "Webhook URLs",
+                    set_subtitle: "One per line, each gets a POST with the JSON of every timer event, saved when leaving the list",
+                    add_action: &reset_button(&sender, Setting::Webhooks),
+                    add_row: &model.webhooks.1,
+                },
+                adw::ActionRow {
+                    set_title: "Test",
+                    #[watch]
+                    set_subtitle: model.webhook_status.as_deref().unwrap_or("Send a test event to every URL"),
+                    add_suffix = &gtk::Button {
+                        set_label: "Send",
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        set_sensitive: !model.config.webhooks.is_empty(),
+                        connect_clicked => SettingsMsg::TestWebhooks,
+                    },
+                },
+                adw::ActionRow {
+                    set_title: "Local API port",
+                    set_subtitle: "Serve status, controls, statistics and events on localhost after a restart, 0 disables it",
+                    add_suffix = &gtk::SpinButton::with_range(0.0, 65535.0, 1.0) {
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        #[block_signal(api_port_handler)]
+                        set_value: model.config.api_port as f64,
+                        connect_value_changed[sender] => move |spin| {
+                            sender.input(SettingsMsg::SetNumber(Setting::ApiPort, spin.value() as u32));
+                        } @api_port_handler,
+                    },
+                    add_suffix: &reset_button(&sender, Setting::ApiPort),
+                },
+            },
+            add = &adw::PreferencesGroup {
+                set_title: "Storage",
+                // toml is the only choice without the sqlite feature
+                set_visible: storage::SQLITE,
+                adw::ComboRow {
+                    set_title: "Statistics backend",
+                    set_subtitle: "Shared by every profile, switching copies the history over",
+                    set_model: Some(&gtk::StringList::new(&["toml", "sqlite"])),
+                    #[watch]
+                    #[block_signal(storage_handler)]
+                    set_selected: match storage::backend() {
+                        storage::Backend::Toml => 0,
+                        storage::Backend::Sqlite => 1,
+                    },
+                    connect_selected_notify[sender] => move |row| {
+                        sender.input(SettingsMsg::SetStorage(match row.selected() {
+                            0 => storage::Backend::Toml,
+                            _ => storage::Backend::Sqlite,
+                        }));
+                    } @storage_handler,
+                },
+            },
+        }
+    }
+
+    fn init(
+        _params: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let buffer = gtk::TextBuffer::new(None);
+        buffer.set_text(&cfg!().break_messages.join("\n"));
+        let buffer_sender = sender.clone();
+        let handler = buffer.connect_changed(move |buffer| {
+            buffer_sender.input(SettingsMsg::SetMessages(lines(buffer)));
+        });
+        let webhooks = gtk::TextBuffer::new(None);
+        webhooks.set_text(&cfg!().webhooks.join("\n"));
+        let webhook_view = gtk::TextView::with_buffer(&webhooks);
+        webhook_view.set_wrap_mode(gtk::WrapMode::Char);
+        webhook_view.set_top_margin(6);
+        webhook_view.set_bottom_margin(6);
+        webhook_view.set_left_margin(12);
+        webhook_view.set_right_margin(12);
+        let focus = gtk::EventControllerFocus::new();
+        let (webhooks_sender, webhooks_buffer) = (sender.clone(), webhooks.clone());
+        focus.connect_leave(move |_| {
+            webhooks_sender.input(SettingsMsg::SetWebhooks(lines(&webhooks_buffer)));
+        });
+        webhook_view.add_controller(&focus);
+        let mut model = SettingsModel {
+            config: cfg!(),
+            error: state::config_error(),
+            weekday_spins: vec![],
+            messages: (buffer, handler),
+            webhooks: (webhooks, webhook_view),
+            webhook_status: None,
+            shortcut_entries: vec![],
+            sound_dialog: None,
+        };
+        let widgets = view_output!();
+
+        for (weekday, name) in (0..).zip(WEEKDAYS) {
+            let spin = gtk::SpinButton::with_range(0.0, 1440.0, 15.0);
+            spin.set_valign(gtk::Align::Center);
+            spin.set_value(weekday_goal(&model.config, weekday) as f64);
+            let spin_sender = sender.clone();
+            let handler = spin.connect_value_changed(move |spin| {
+                spin_sender.input(SettingsMsg::SetNumber(
+                    Setting::WeekdayGoal(weekday),
+                    spin.value() as u32,
+                ));
+            });
+            let row = adw::ActionRow::new();
+            row.set_title(name);
+            row.add_suffix(&spin);
+            row.add_suffix(&reset_button(&sender, Setting::WeekdayGoal(weekday)));
+            widgets.weekdays.add_row(&row);
+            model.weekday_spins.push((spin, handler));
+        }
+
+        for action in shortcuts::Action::ALL {
+            let entry = gtk::Entry::new();
+            entry.set_valign(gtk::Align::Center);
+            entry.set_text(action.binding(&model.config.shortcuts));
+            let entry_sender = sender.clone();
+            let handler = entry.connect_changed(move |entry| {
+                entry_sender.input(SettingsMsg::SetShortcut(action, entry.text().to_string()));
+            });
+            let focus = gtk::EventControllerFocus::new();
+            let focus_sender = sender.clone();
+            focus.connect_leave(move |_| focus_sender.input(SettingsMsg::Revert));
+            entry.add_controller(&focus);
+            let row = adw::ActionRow::new();
+            row.set_title(action.title());
+            row.add_suffix(&entry);
+            row.add_suffix(&reset_button(&sender, Setting::Shortcut(action)));
+            widgets.shortcuts_group.add(&row);
+            model.shortcut_entries.push((action, entry, handler));
+        }
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            SettingsMsg::SetBool(setting, value) => {
+                self.edit(&sender, |config| setting.set_bool(config, value))
+            }
+            SettingsMsg::SetNumber(setting, value) => {
+                self.edit(&sender, |config| setting.set_number(config, value))
+            }
+            SettingsMsg::SetSound(path) => self.edit(&sender, |config| config.sound = path),
+            // shared by all profiles, so it lives in the statistics rather than the config
+            SettingsMsg::SetStorage(backend) => match storage::set_backend(backend) {
+                Ok(()) => {
+                    self.error = None;
+                    sender.output(SettingsOutput::Saved).unwrap();
+                }
+                Err(e) => self.error = Some(format!("{:#}", e)),
+            },
+            SettingsMsg::SetTheme(value) => self.edit(&sender, |config| config.theme = value),
+            SettingsMsg::SetColorScheme(value) => {
+                self.edit(&sender, |config| config.color_scheme = value)
+            }
+            SettingsMsg::SetMessages(value) => {
+                self.edit(&sender, |config| config.break_messages = value)
+            }
+            SettingsMsg::SetWebhooks(value) => self.edit(&sender, |config| config.webhooks = value),
+            // checked on its own first, a binding GTK can't parse would only be skipped later
+            SettingsMsg::SetShortcut(_, value) if !shortcuts::parses(&value) => {
+                self.error = Some(format!("shortcut {value:?} is not a valid accelerator"));
+            }
+            SettingsMsg::SetShortcut(action, value) => self.edit(&sender, |config| {
+                *action.binding_mut(&mut config.shortcuts) = value
+            }),
+            SettingsMsg::Reset(setting) => self.edit(&sender, |config| setting.reset(config)),
+            SettingsMsg::ChooseSound => {
+                let dialog = gtk::FileChooserNative::new(
+                    Some("Choose a sound"),
+                    None::<&gtk::Window>,
+                    gtk::FileChooserAction::Open,
+                    Some("Choose"),
+                    Some("Cancel"),
+                );
+                let filter = gtk::FileFilter::new();
+                filter.set_name(Some("Audio"));
+                filter.add_mime_type("audio/*");
+                dialog.add_filter(&filter);
+                dialog.connect_response(move |dialog, response| {
+                    if response == gtk::ResponseType::Accept {
+                        if let Some(path) = dialog.file().and_then(|file| file.path()) {
+                            sender.input(SettingsMsg::SetSound(path));
+                        }
+                    }
+                });
+                dialog.show();
+                self.sound_dialog = Some(dialog);
+            }
+            SettingsMsg::TestWebhooks => {
+                self.webhook_status = Some("Sending…".to_string());
+                let urls = self.config.webhooks.clone();
+                std::thread::spawn(move || {
+                    sender.input(SettingsMsg::WebhooksTested(webhooks::test(&urls)))
+                });
+            }
+            SettingsMsg::WebhooksTested(status) => self.webhook_status = Some(status),
+            // `post_view` puts the text back once nothing is typed into it
+            SettingsMsg::Revert => (),
+            SettingsMsg::Reload => {
+                self.config = cfg!();
+                self.error = state::config_error();
+            }
+        }
+    }
+
+    // the weekday rows, messages and shortcuts aren't plain properties the macro could keep up to date
+    fn post_view() {
+        // entries being typed into keep their text, it may not be a valid binding yet
+        for (action, entry, handler) in model.shortcut_entries.iter() {
+            let binding = action.binding(&model.config.shortcuts);
+            if entry.focus_child().is_none() && entry.text() != binding {
+                entry.block_signal(handler);
+                entry.set_text(binding);
+                entry.unblock_signal(handler);
+            }
+            // text that differs from the binding in effect was rejected
+            if entry.text() == binding {
+                entry.remove_css_class("error");
+            } else {
+                entry.add_css_class("error");
+            }
+        }
+        let (buffer, handler) = &model.messages;
+        if lines(buffer) != model.config.break_messages {
+            buffer.block_signal(handler);
+            buffer.set_text(&model.config.break_messages.join("\n"));
+            buffer.unblock_signal(handler);
+        }
+        let (buffer, view) = &model.webhooks;
+        if !view.has_focus() && lines(buffer) != model.config.webhooks {
+            buffer.set_text(&model.config.webhooks.join("\n"));
+        }
+        for (weekday, (spin, handler)) in (0..).zip(model.weekday_spins.iter()) {
+            spin.block_signal(handler);
+            spin.set_value(weekday_goal(&model.config, weekday) as f64);
+            spin.unblock_signal(handler);
+        }
+    }
+}
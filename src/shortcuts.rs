@@ -0,0 +1,206 @@
+//! Keyboard shortcuts of both windows and the shortcuts help window.
+//!
+//! Bindings are GTK accelerator strings like `<Control>m` kept in `Config::shortcuts`,
+//! an empty string leaves the action unbound. The timer bindings need a modifier by default,
+//! bare keys would fire while typing into an entry or be taken by a focused button.
+use crate::cfg;
+use gtk::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Shortcuts {
+    pub toggle: String,
+    #[serde(rename = "break")]
+    pub take_break: String,
+    pub reset: String,
+    pub flowtime: String,
+    pub settings: String,
+    pub statistics: String,
+    pub mini: String,
+    pub help: String,
+}
+
+impl Default for Shortcuts {
+    fn default() -> Self {
+        Self {
+            toggle: "<Control>space".to_string(),
+            take_break: "<Control>b".to_string(),
+            reset: "<Control>r".to_string(),
+            flowtime: "<Control>1".to_string(),
+            settings: "<Control>2".to_string(),
+            statistics: "<Control>3".to_string(),
+            mini: "<Control>m".to_string(),
+            help: "<Control>question".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Toggle,
+    Break,
+    Reset,
+    FlowTime,
+    Settings,
+    Statistics,
+    Mini,
+    Help,
+}
+
+impl Action {
+    pub const ALL: [Action; 8] = [
+        Action::Toggle,
+        Action::Break,
+        Action::Reset,
+        Action::FlowTime,
+        Action::Settings,
+        Action::Statistics,
+        Action::Mini,
+        Action::Help,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Action::Toggle => "Start or pause the timer",
+            Action::Break => "Take a break",
+            Action::Reset => "Reset the session",
+            Action::FlowTime => "Show the timer",
+            Action::Settings => "Show the settings",
+            Action::Statistics => "Show the statistics",
+            Action::Mini => "Switch to or from the mini window",
+            Action::Help => "Show the keyboard shortcuts",
+        }
+    }
+
+    pub fn binding(self, shortcuts: &Shortcuts) -> &str {
+        match self {
+            Action::Toggle => &shortcuts.toggle,
+            Action::Break => &shortcuts.take_break,
+            Action::Reset => &shortcuts.reset,
+            Action::FlowTime => &shortcuts.flowtime,
+            Action::Settings => &shortcuts.settings,
+            Action::Statistics => &shortcuts.statistics,
+            Action::Mini => &shortcuts.mini,
+            Action::Help => &shortcuts.help,
+        }
+    }
+
+    pub fn binding_mut(self, shortcuts: &mut Shortcuts) -> &mut String {
+        match self {
+            Action::Toggle => &mut shortcuts.toggle,
+            Action::Break => &mut shortcuts.take_break,
+            Action::Reset => &mut shortcuts.reset,
+            Action::FlowTime => &mut shortcuts.flowtime,
+            Action::Settings => &mut shortcuts.settings,
+            Action::Statistics => &mut shortcuts.statistics,
+            Action::Mini => &mut shortcuts.mini,
+            Action::Help => &mut shortcuts.help,
+        }
+    }
+}
+
+const MODIFIERS: [&str; 8] = [
+    "Control", "Ctrl", "Primary", "Shift", "Alt", "Super", "Meta", "Hyper",
+];
+
+/// Whether `binding` looks like `<Modifier>...key`, without GTK so it works before it is
+/// initialized and off the main thread
+fn well_formed(binding: &str) -> bool {
+    let mut rest = binding;
+    while let Some(x) = rest.strip_prefix('<') {
+        match x.split_once('>') {
+            Some((modifier, after))
+                if MODIFIERS.iter().any(|m| m.eq_ignore_ascii_case(modifier)) =>
+            {
+                rest = after
+            }
+            _ => return false,
+        }
+    }
+    !rest.is_empty() && !rest.contains(['<', '>'])
+}
+
+/// Whether GTK accepts `binding`, unlike `well_formed` this checks the key name too
+pub fn parses(binding: &str) -> bool {
+    binding.is_empty() || gtk::accelerator_parse(binding).is_some()
+}
+
+/// Readable descriptions of malformed bindings and of bindings used twice
+pub fn validate(shortcuts: &Shortcuts) -> Vec<String> {
+    let mut errors = vec![];
+    for (i, action) in Action::ALL.iter().enumerate() {
+        let binding = action.binding(shortcuts);
+        if binding.is_empty() {
+            continue;
+        }
+        if !well_formed(binding) {
+            errors.push(format!("shortcut {binding:?} is not a valid accelerator"));
+        } else if Action::ALL[..i]
+            .iter()
+            .any(|x| x.binding(shortcuts) == binding)
+        {
+            errors.push(format!("shortcut {binding:?} is bound twice"));
+        }
+    }
+    errors
+}
+
+/// Replaces the shortcuts of `controller` with the configured ones, each calling `run`
+pub fn bind(controller: &gtk::ShortcutController, run: Rc<dyn Fn(Action)>) {
+    while let Some(shortcut) = controller
+        .item(0)
+        .and_then(|x| x.downcast::<gtk::Shortcut>().ok())
+    {
+        controller.remove_shortcut(&shortcut);
+    }
+    let shortcuts = cfg!().shortcuts;
+    for action in Action::ALL {
+        // key names aren't checked by `validate`, unknown ones are skipped here
+        if let Some(trigger) = gtk::ShortcutTrigger::parse_string(action.binding(&shortcuts)) {
+            let run = run.clone();
+            controller.add_shortcut(&gtk::Shortcut::new(
+                Some(trigger),
+                Some(gtk::CallbackAction::new(move |_, _| {
+                    run(action);
+                    true
+                })),
+            ));
+        }
+    }
+}
+
+/// A `gtk::ShortcutsWindow` listing the current bindings
+pub fn window() -> gtk::ShortcutsWindow {
+    let shortcuts = cfg!().shortcuts;
+    let rows: String = Action::ALL
+        .iter()
+        .filter(|x| !x.binding(&shortcuts).is_empty())
+        .map(|x| {
+            format!(
+                r#"<child><object class="GtkShortcutsShortcut">
+                    <property name="title">{}</property>
+                    <property name="accelerator">{}</property>
+                </object></child>"#,
+                x.title(),
+                gtk::glib::markup_escape_text(x.binding(&shortcuts))
+            )
+        })
+        .collect();
+    let ui = format!(
+        r#"<interface>
+            <object class="GtkShortcutsWindow" id="window">
+                <property name="modal">1</property>
+                <child><object class="GtkShortcutsSection">
+                    <property name="section-name">shortcuts</property>
+                    <child><object class="GtkShortcutsGroup">
+                        <property name="title">Flowtime</property>
+                        {rows}
+                    </object></child>
+                </object></child>
+            </object>
+        </interface>"#
+    );
+    gtk::Builder::from_string(&ui).object("window").unwrap()
+}
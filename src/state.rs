@@ -0,0 +1,201 @@
+//! In memory copies of the config and statistics files.
+//!
+//! Both files are loaded once, written through `save_config`/`save_stats`, and reloaded
+//! by `watch` when something else changes them on disk or switches the active profile.
+//! The statistics database isn't cached, `watch` only reports when someone else wrote to it.
+//! An invalid file is never saved over, the defaults used meanwhile stay in memory.
+use crate::{profile, Config, Stats};
+use anyhow::{bail, Context};
+use gtk::gio;
+use gtk::prelude::*;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::time::SystemTime;
+
+struct Cached<T> {
+    value: T,
+    /// Config name of the file the value came from
+    name: String,
+    /// Modification time of the file when it was last loaded or saved by us
+    modified: Option<SystemTime>,
+    /// Why the file couldn't be loaded, `value` is then a stand-in that mustn't replace it
+    broken: Option<String>,
+}
+
+lazy_static! {
+    static ref CONFIG: RwLock<Cached<Config>> = RwLock::new(cached(
+        profile::config_name(&profile::active()),
+        load_config,
+    ));
+    static ref STATS: RwLock<Cached<Stats>> =
+        RwLock::new(cached("statistics".to_string(), load_stats));
+    /// Modification time of the statistics database after our last write
+    static ref DATABASE_MODIFIED: Mutex<Option<SystemTime>> =
+        Mutex::new(file_modified(&database_path()));
+}
+
+thread_local! {
+    static MONITOR: RefCell<Option<gio::FileMonitor>> = RefCell::new(None);
+}
+
+pub fn path(name: &str) -> PathBuf {
+    confy::get_configuration_file_path("flowtime", Some(name)).unwrap()
+}
+
+pub fn database_path() -> PathBuf {
+    path("statistics").with_extension("sqlite")
+}
+
+fn file_modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|x| x.modified()).ok()
+}
+
+fn modified(name: &str) -> Option<SystemTime> {
+    file_modified(&path(name))
+}
+
+fn load_config(name: &str) -> anyhow::Result<Config> {
+    crate::config::load(name)
+}
+
+fn load_stats(name: &str) -> anyhow::Result<Stats> {
+    confy::load("flowtime", Some(name))
+        .with_context(|| format!("invalid statistics {}", path(name).display()))
+}
+
+/// Loads `name`, falling back to the defaults if it is invalid
+fn cached<T: Default>(name: String, load: fn(&str) -> anyhow::Result<T>) -> Cached<T> {
+    let (value, broken) = match load(&name) {
+        Ok(x) => (x, None),
+        Err(e) => {
+            eprintln!(
+                "flowtime: {:#}\nflowtime: using the defaults until it is fixed",
+                e
+            );
+            (T::default(), Some(format!("{:#}", e)))
+        }
+    };
+    Cached {
+        value,
+        modified: modified(&name),
+        name,
+        broken,
+    }
+}
+
+/// Writes `value` unless the file is broken, `what` names the file's content in errors.
+/// A failed write still keeps `value` in memory, so the next save can catch up.
+fn store<T: Serialize>(cache: &RwLock<Cached<T>>, value: T, what: &str) -> anyhow::Result<()> {
+    let mut cache = cache.write().unwrap();
+    if let Some(e) = &cache.broken {
+        bail!(
+            "{} aren't saved until {} is fixed\n{}",
+            what,
+            path(&cache.name).display(),
+            e
+        );
+    }
+    let result = confy::store("flowtime", Some(cache.name.as_str()), &value)
+        .with_context(|| format!("failed to save {}", path(&cache.name).display()));
+    cache.value = value;
+    cache.modified = modified(&cache.name);
+    result
+}
+
+/// Reloads from `name` if it isn't the cached file or was modified by someone else,
+/// returns whether it did
+fn reload<T>(name: &str, cache: &RwLock<Cached<T>>, load: fn(&str) -> anyhow::Result<T>) -> bool {
+    let modified = modified(name);
+    {
+        let cache = cache.read().unwrap();
+        if name == cache.name && modified == cache.modified {
+            return false;
+        }
+    }
+    let mut cache = cache.write().unwrap();
+    cache.name = name.to_string();
+    cache.modified = modified;
+    match load(name) {
+        Ok(value) => {
+            cache.value = value;
+            cache.broken = None;
+            true
+        }
+        // keep the last good state around while the file is being edited
+        Err(e) => {
+            eprintln!("flowtime: failed to reload {}: {:#}", name, e);
+            cache.broken = Some(format!("{:#}", e));
+            false
+        }
+    }
+}
+
+pub fn config() -> Config {
+    CONFIG.read().unwrap().value.clone()
+}
+
+pub fn stats() -> Stats {
+    STATS.read().unwrap().value.clone()
+}
+
+/// Why the config file of the active profile is unusable, if it is
+pub fn config_error() -> Option<String> {
+    CONFIG.read().unwrap().broken.clone()
+}
+
+/// Saves the config, unless its file is invalid and would be lost by that
+pub fn save_config(config: Config) -> anyhow::Result<()> {
+    store(&CONFIG, config, "settings")
+}
+
+/// Saves the statistics, unless their file is invalid and would be lost by that
+pub fn save_stats(stats: Stats) -> anyhow::Result<()> {
+    store(&STATS, stats, "statistics")
+}
+
+/// Loads the config of the active profile right away, returns whether it changed
+pub fn reload_config() -> bool {
+    reload(
+        &profile::config_name(&profile::active()),
+        &CONFIG,
+        load_config,
+    )
+}
+
+/// Notes the database as written by us, so `watch` doesn't report it
+pub fn database_saved() {
+    *DATABASE_MODIFIED.lock().unwrap() = file_modified(&database_path());
+}
+
+/// Whether someone else wrote to the database since we last did
+fn database_changed() -> bool {
+    let modified = file_modified(&database_path());
+    let mut last = DATABASE_MODIFIED.lock().unwrap();
+    std::mem::replace(&mut *last, modified) != modified
+}
+
+/// Monitors the config directory, calling `changed` after a file was reloaded or the
+/// database changed. Has to run on the main thread.
+pub fn watch(changed: impl Fn() + 'static) {
+    let directory = path("flowtime").parent().unwrap().to_path_buf();
+    let monitor = match gio::File::for_path(&directory)
+        .monitor_directory(gio::FileMonitorFlags::NONE, None::<&gio::Cancellable>)
+    {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("flowtime: can't watch {}: {}", directory.display(), e);
+            return;
+        }
+    };
+    // the active profile file is in the same directory, so switches are noticed too
+    monitor.connect_changed(move |_, _, _, _| {
+        // everything has to be checked, so no short circuiting
+        if reload_config() | reload("statistics", &STATS, load_stats) | database_changed() {
+            changed();
+        }
+    });
+    MONITOR.with(|x| *x.borrow_mut() = Some(monitor));
+}
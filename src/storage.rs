@@ -0,0 +1,481 @@
+//! Query layer over the session log and the per day totals.
+//!
+//! Monthly totals always live in the confy statistics file, while days and sessions
+//! are kept either in the same file or, with the `sqlite` feature, in an indexed database.
+//! The choice is kept in the statistics file as well, so every profile sees the same history.
+use crate::{import, stat, state, DayStat, Interruption, Session, Stats};
+#[cfg(not(feature = "sqlite"))]
+use anyhow::bail;
+use chrono::NaiveDate;
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(PartialEq, Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Toml,
+    Sqlite,
+}
+
+#[cfg(feature = "sqlite")]
+pub fn backend() -> Backend {
+    stat!().storage.unwrap_or_default()
+}
+
+/// Builds without the `sqlite` feature always use toml
+#[cfg(not(feature = "sqlite"))]
+pub fn backend() -> Backend {
+    Backend::Toml
+}
+
+/// Whether this build can keep the statistics in SQLite
+pub const SQLITE: bool = std::cfg!(feature = "sqlite");
+
+/// Switches to `backend`, copying the days, sessions and interruptions over so it starts
+/// with the whole history. The copy replaces whatever it held from an earlier switch.
+pub fn set_backend(backend: Backend) -> anyhow::Result<()> {
+    let mut stats = stat!();
+    match (self::backend(), backend) {
+        (Backend::Toml, Backend::Toml) | (Backend::Sqlite, Backend::Sqlite) => (),
+        #[cfg(feature = "sqlite")]
+        (Backend::Toml, Backend::Sqlite) => sqlite::replace(&stats)?,
+        #[cfg(feature = "sqlite")]
+        (Backend::Sqlite, Backend::Toml) => {
+            (stats.days, stats.sessions, stats.interruptions) = sqlite::history()?;
+        }
+        #[cfg(not(feature = "sqlite"))]
+        _ => bail!("this build has no SQLite support, it needs the sqlite feature"),
+    }
+    stats.storage = Some(backend);
+    state::save_stats(stats)
+}
+
+/// Takes over the backend an old profile config chose, unless one was chosen already.
+/// Its history is already in that backend, so nothing is copied.
+pub fn adopt(backend: Backend) -> anyhow::Result<()> {
+    let mut stats = stat!();
+    if stats.storage.is_some() {
+        return Ok(());
+    }
+    stats.storage = Some(backend);
+    state::save_stats(stats)
+}
+
+fn add_to_day(days: &mut Vec<DayStat>, date: NaiveDate, f: impl FnOnce(&mut DayStat)) {
+    match days.iter_mut().find(|day| day.date == date) {
+        Some(day) => f(day),
+        None => {
+            let mut day = DayStat {
+                date,
+                break_second: 0,
+                work_second: 0,
+            };
+            f(&mut day);
+            days.push(day);
+        }
+    }
+}
+
+pub fn days() -> Vec<DayStat> {
+    match backend() {
+        Backend::Toml => stat!().days,
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => sqlite::days(),
+        #[cfg(not(feature = "sqlite"))]
+        Backend::Sqlite => unreachable!(),
+    }
+}
+
+pub fn update_day(date: NaiveDate, f: impl FnOnce(&mut DayStat)) -> anyhow::Result<()> {
+    match backend() {
+        Backend::Toml => {
+            let mut stats = stat!();
+            add_to_day(&mut stats.days, date, f);
+            state::save_stats(stats)
+        }
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => sqlite::update_day(date, f),
+        #[cfg(not(feature = "sqlite"))]
+        Backend::Sqlite => unreachable!(),
+    }
+}
+
+/// Sessions that started between `from` and `to`, both inclusive, ordered by start
+pub fn sessions(from: Option<NaiveDate>, to: Option<NaiveDate>) -> Vec<Session> {
+    match backend() {
+        Backend::Toml => crate::export::sessions_between(&stat!().sessions, from, to),
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => sqlite::sessions(from, to),
+        #[cfg(not(feature = "sqlite"))]
+        Backend::Sqlite => unreachable!(),
+    }
+}
+
+pub fn add_session(session: Session) -> anyhow::Result<()> {
+    match backend() {
+        Backend::Toml => {
+            let mut stats = stat!();
+            stats.sessions.push(session);
+            state::save_stats(stats)
+        }
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => sqlite::import(&[session], false),
+        #[cfg(not(feature = "sqlite"))]
+        Backend::Sqlite => unreachable!(),
+    }
+}
+
+pub fn add_interruption(interruption: Interruption) -> anyhow::Result<()> {
+    match backend() {
+        Backend::Toml => {
+            let mut stats = stat!();
+            stats.interruptions.push(interruption);
+            state::save_stats(stats)
+        }
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => sqlite::add_interruption(&interruption),
+        #[cfg(not(feature = "sqlite"))]
+        Backend::Sqlite => unreachable!(),
+    }
+}
+
+/// Adds sessions to the session log along with the day and month totals, in one write
+pub fn import(sessions: &[Session]) -> anyhow::Result<()> {
+    let mut stats: Stats = stat!();
+    import::apply_months(&mut stats, sessions);
+    match backend() {
+        Backend::Toml => {
+            for session in sessions {
+                add_to_day(&mut stats.days, session.start.date_naive(), |day| {
+                    day.work_second += session.work_second;
+                    day.break_second += session.break_second;
+                });
+            }
+            stats.sessions.extend(sessions.iter().cloned());
+            stats.sessions.sort_by_key(|x| x.start);
+            stats.days.sort_by_key(|x| x.date);
+        }
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => sqlite::import(sessions, true)?,
+        #[cfg(not(feature = "sqlite"))]
+        Backend::Sqlite => unreachable!(),
+    }
+    state::save_stats(stats)
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::*;
+    use anyhow::Context;
+    use chrono::{DateTime, Duration, Local, TimeZone};
+    use lazy_static::lazy_static;
+    use rusqlite::{params, Connection, OptionalExtension, Row};
+    use std::sync::Mutex;
+
+    /// Each entry upgrades the schema by one version, tracked by `PRAGMA user_version`
+    const MIGRATIONS: &[&str] = &[
+        "
+        CREATE TABLE tags (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE
+        );
+        CREATE TABLE sessions (
+            id INTEGER PRIMARY KEY,
+            start INTEGER NOT NULL,
+            end INTEGER NOT NULL,
+            work_second INTEGER NOT NULL,
+            break_second INTEGER NOT NULL,
+            tag_id INTEGER REFERENCES tags(id)
+        );
+        CREATE INDEX sessions_start ON sessions(start);
+        CREATE INDEX sessions_tag ON sessions(tag_id);
+        CREATE TABLE days (
+            date TEXT PRIMARY KEY,
+            work_second INTEGER NOT NULL,
+            break_second INTEGER NOT NULL
+        );
+        CREATE TABLE interruptions (
+            id INTEGER PRIMARY KEY,
+            time INTEGER NOT NULL,
+            note TEXT NOT NULL DEFAULT ''
+        );
+        CREATE INDEX interruptions_time ON interruptions(time);
+    ",
+        "
+        ALTER TABLE sessions ADD COLUMN profile TEXT NOT NULL DEFAULT '';
+    ",
+    ];
+
+    lazy_static! {
+        static ref CONNECTION: Mutex<Option<Connection>> = Mutex::new(None);
+    }
+
+    fn open() -> anyhow::Result<Connection> {
+        let mut connection = Connection::open(state::database_path())?;
+        let version: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        let transaction = connection.transaction()?;
+        for migration in MIGRATIONS.iter().skip(version) {
+            transaction.execute_batch(migration)?;
+        }
+        transaction.pragma_update(None, "user_version", MIGRATIONS.len())?;
+        // a new database starts with everything recorded in the toml file so far
+        if version == 0 {
+            let stats = stat!();
+            for session in stats.sessions.iter() {
+                insert_session(&transaction, session)?;
+            }
+            for day in stats.days.iter() {
+                upsert_day(&transaction, day)?;
+            }
+            for interruption in stats.interruptions.iter() {
+                insert_interruption(&transaction, interruption)?;
+            }
+        }
+        transaction.commit()?;
+        state::database_saved();
+        Ok(connection)
+    }
+
+    fn try_with<T>(f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>) -> anyhow::Result<T> {
+        let mut connection = CONNECTION.lock().unwrap();
+        if connection.is_none() {
+            // opening is tried again next time, the database may just be locked
+            *connection = Some(open().context("failed to open the statistics database")?);
+        }
+        f(connection.as_mut().unwrap()).context("statistics database query failed")
+    }
+
+    /// Runs `f` on the database, failures are printed and give an empty result instead
+    fn with<T: Default>(f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>) -> T {
+        try_with(f).unwrap_or_else(|e| {
+            eprintln!("flowtime: {:#}", e);
+            T::default()
+        })
+    }
+
+    /// Runs statements that write, noting the change as ours for `state::watch`
+    fn with_write(f: impl FnOnce(&mut Connection) -> rusqlite::Result<()>) -> anyhow::Result<()> {
+        try_with(f)?;
+        state::database_saved();
+        Ok(())
+    }
+
+    fn insert_session(connection: &Connection, session: &Session) -> rusqlite::Result<()> {
+        let tag_id: Option<i64> = if session.tag.is_empty() {
+            None
+        } else {
+            connection.execute(
+                "INSERT OR IGNORE INTO tags (name) VALUES (?1)",
+                params![session.tag],
+            )?;
+            Some(connection.query_row(
+                "SELECT id FROM tags WHERE name = ?1",
+                params![session.tag],
+                |row| row.get(0),
+            )?)
+        };
+        connection.execute(
+            "INSERT INTO sessions (start, end, work_second, break_second, tag_id, profile)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                session.start.timestamp(),
+                session.end.timestamp(),
+                session.work_second,
+                session.break_second,
+                tag_id,
+                session.profile
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn insert_interruption(
+        connection: &Connection,
+        interruption: &Interruption,
+    ) -> rusqlite::Result<()> {
+        connection.execute(
+            "INSERT INTO interruptions (time, note) VALUES (?1, ?2)",
+            params![interruption.time.timestamp(), interruption.note],
+        )?;
+        Ok(())
+    }
+
+    fn upsert_day(connection: &Connection, day: &DayStat) -> rusqlite::Result<()> {
+        connection.execute(
+            "INSERT INTO days (date, work_second, break_second) VALUES (?1, ?2, ?3)
+             ON CONFLICT (date) DO UPDATE SET
+                work_second = excluded.work_second,
+                break_second = excluded.break_second",
+            params![day.date.to_string(), day.work_second, day.break_second],
+        )?;
+        Ok(())
+    }
+
+    fn day(connection: &Connection, date: NaiveDate) -> rusqlite::Result<DayStat> {
+        let day = connection
+            .query_row(
+                "SELECT work_second, break_second FROM days WHERE date = ?1",
+                params![date.to_string()],
+                |row| {
+                    Ok(DayStat {
+                        date,
+                        work_second: row.get(0)?,
+                        break_second: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(day.unwrap_or(DayStat {
+            date,
+            work_second: 0,
+            break_second: 0,
+        }))
+    }
+
+    fn timestamp(x: i64) -> DateTime<Local> {
+        Local.timestamp_opt(x, 0).unwrap()
+    }
+
+    fn session_from_row(row: &Row) -> rusqlite::Result<Session> {
+        Ok(Session {
+            start: timestamp(row.get(0)?),
+            end: timestamp(row.get(1)?),
+            work_second: row.get(2)?,
+            break_second: row.get(3)?,
+            tag: row.get(4)?,
+            profile: row.get(5)?,
+        })
+    }
+
+    fn select_days(connection: &Connection) -> rusqlite::Result<Vec<DayStat>> {
+        let mut statement =
+            connection.prepare("SELECT date, work_second, break_second FROM days ORDER BY date")?;
+        let days = statement
+            .query_map([], |row| {
+                let date: String = row.get(0)?;
+                Ok(DayStat {
+                    date: date.parse().map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            0,
+                            rusqlite::types::Type::Text,
+                            Box::new(e),
+                        )
+                    })?,
+                    work_second: row.get(1)?,
+                    break_second: row.get(2)?,
+                })
+            })?
+            .collect();
+        days
+    }
+
+    /// Sessions that started from `from` up to but excluding `to`, as timestamps
+    fn select_sessions(
+        connection: &Connection,
+        from: i64,
+        to: i64,
+    ) -> rusqlite::Result<Vec<Session>> {
+        let mut statement = connection.prepare(
+            "SELECT s.start, s.end, s.work_second, s.break_second, COALESCE(t.name, ''), s.profile
+             FROM sessions s LEFT JOIN tags t ON t.id = s.tag_id
+             WHERE s.start >= ?1 AND s.start < ?2
+             ORDER BY s.start",
+        )?;
+        let sessions = statement
+            .query_map(params![from, to], session_from_row)?
+            .collect();
+        sessions
+    }
+
+    fn select_interruptions(connection: &Connection) -> rusqlite::Result<Vec<Interruption>> {
+        let mut statement =
+            connection.prepare("SELECT time, note FROM interruptions ORDER BY time")?;
+        let interruptions = statement
+            .query_map([], |row| {
+                Ok(Interruption {
+                    time: timestamp(row.get(0)?),
+                    note: row.get(1)?,
+                })
+            })?
+            .collect();
+        interruptions
+    }
+
+    pub fn days() -> Vec<DayStat> {
+        with(|connection| select_days(connection))
+    }
+
+    pub fn update_day(date: NaiveDate, f: impl FnOnce(&mut DayStat)) -> anyhow::Result<()> {
+        with_write(|connection| {
+            let transaction = connection.transaction()?;
+            let mut day = day(&transaction, date)?;
+            f(&mut day);
+            upsert_day(&transaction, &day)?;
+            transaction.commit()
+        })
+    }
+
+    pub fn sessions(from: Option<NaiveDate>, to: Option<NaiveDate>) -> Vec<Session> {
+        let midnight = |date: NaiveDate| {
+            Local
+                .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+                .earliest()
+                .map_or(0, |x| x.timestamp())
+        };
+        let from = from.map_or(i64::MIN, midnight);
+        let to = to.map_or(i64::MAX, |x| midnight(x + Duration::days(1)));
+        with(|connection| select_sessions(connection, from, to))
+    }
+
+    pub fn add_interruption(interruption: &Interruption) -> anyhow::Result<()> {
+        with_write(|connection| insert_interruption(connection, interruption))
+    }
+
+    /// Inserts the sessions, adding them to the day totals when `days` is set
+    pub fn import(sessions: &[Session], days: bool) -> anyhow::Result<()> {
+        with_write(|connection| {
+            let transaction = connection.transaction()?;
+            for session in sessions {
+                insert_session(&transaction, session)?;
+                if days {
+                    let mut day = day(&transaction, session.start.date_naive())?;
+                    day.work_second += session.work_second;
+                    day.break_second += session.break_second;
+                    upsert_day(&transaction, &day)?;
+                }
+            }
+            transaction.commit()
+        })
+    }
+
+    /// Days, sessions and interruptions, failing rather than coming back empty
+    pub fn history() -> anyhow::Result<(Vec<DayStat>, Vec<Session>, Vec<Interruption>)> {
+        try_with(|connection| {
+            Ok((
+                select_days(connection)?,
+                select_sessions(connection, i64::MIN, i64::MAX)?,
+                select_interruptions(connection)?,
+            ))
+        })
+    }
+
+    /// Replaces the days, sessions and interruptions with the ones in `stats`
+    pub fn replace(stats: &Stats) -> anyhow::Result<()> {
+        with_write(|connection| {
+            let transaction = connection.transaction()?;
+            transaction.execute_batch(
+                "DELETE FROM sessions; DELETE FROM days; DELETE FROM interruptions;",
+            )?;
+            for session in stats.sessions.iter() {
+                insert_session(&transaction, session)?;
+            }
+            for day in stats.days.iter() {
+                upsert_day(&transaction, day)?;
+            }
+            for interruption in stats.interruptions.iter() {
+                insert_interruption(&transaction, interruption)?;
+            }
+            transaction.commit()
+        })
+    }
+}
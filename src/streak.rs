@@ -0,0 +1,202 @@
+use crate::DayStat;
+use chrono::{Datelike, Duration, NaiveDate};
+use std::collections::HashMap;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum DayResult {
+    Met,
+    Missed,
+    /// Days without a goal neither extend nor break a streak
+    Skipped,
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+pub struct Streaks {
+    pub current: u32,
+    pub longest: u32,
+}
+
+/// Judges a day by its work time against its goal
+pub fn judge(work_second: u32, goal_minutes: u32, requires_goal: bool) -> DayResult {
+    if goal_minutes == 0 {
+        if work_second > 0 && !requires_goal {
+            DayResult::Met
+        } else {
+            DayResult::Skipped
+        }
+    } else if (requires_goal && work_second >= goal_minutes * 60)
+        || (!requires_goal && work_second > 0)
+    {
+        DayResult::Met
+    } else {
+        DayResult::Missed
+    }
+}
+
+/// Results of every day from the first recorded one up to `today`
+fn results(
+    days: &[DayStat],
+    today: NaiveDate,
+    goal_minutes: impl Fn(NaiveDate) -> u32,
+    requires_goal: bool,
+) -> Vec<(NaiveDate, DayResult)> {
+    let first = match days.iter().map(|day| day.date).min() {
+        Some(first) => first,
+        None => return vec![],
+    };
+    let work: HashMap<NaiveDate, u32> =
+        days.iter().map(|day| (day.date, day.work_second)).collect();
+    let mut output = vec![];
+    let mut date = first;
+    while date <= today {
+        let work_second = work.get(&date).copied().unwrap_or(0);
+        output.push((date, judge(work_second, goal_minutes(date), requires_goal)));
+        date += Duration::days(1);
+    }
+    output
+}
+
+pub fn streaks(
+    days: &[DayStat],
+    today: NaiveDate,
+    goal_minutes: impl Fn(NaiveDate) -> u32,
+    requires_goal: bool,
+) -> Streaks {
+    let results = results(days, today, goal_minutes, requires_goal);
+    let mut streaks = Streaks::default();
+
+    let mut running = 0;
+    for (_, result) in results.iter() {
+        match result {
+            DayResult::Met => {
+                running += 1;
+                streaks.longest = streaks.longest.max(running);
+            }
+            DayResult::Missed => running = 0,
+            DayResult::Skipped => (),
+        }
+    }
+
+    for (date, result) in results.iter().rev() {
+        match result {
+            DayResult::Met => streaks.current += 1,
+            // today is still in progress so it can't break the streak yet
+            DayResult::Missed if *date == today => (),
+            DayResult::Missed => break,
+            DayResult::Skipped => (),
+        }
+    }
+    streaks
+}
+
+/// Fraction of days met for each weekday starting from monday, `None` if the weekday never had a goal
+pub fn weekday_consistency(
+    days: &[DayStat],
+    today: NaiveDate,
+    goal_minutes: impl Fn(NaiveDate) -> u32,
+    requires_goal: bool,
+) -> [Option<f64>; 7] {
+    let mut met = [0u32; 7];
+    let mut total = [0u32; 7];
+    for (date, result) in results(days, today, goal_minutes, requires_goal) {
+        let weekday = date.weekday().num_days_from_monday() as usize;
+        match result {
+            DayResult::Met => {
+                met[weekday] += 1;
+                total[weekday] += 1;
+            }
+            DayResult::Missed if date != today => total[weekday] += 1,
+            _ => (),
+        }
+    }
+    std::array::from_fn(|weekday| {
+        (total[weekday] != 0).then(|| met[weekday] as f64 / total[weekday] as f64)
+    })
+}
+
+/// Intensity from 0 to 4 used for coloring heatmap cells
+pub fn heat_level(work_second: u32, goal_minutes: u32) -> u8 {
+    if work_second == 0 {
+        return 0;
+    }
+    // without a goal four hours of work is the highest level
+    let goal_second = if goal_minutes == 0 {
+        4 * 3600
+    } else {
+        goal_minutes * 60
+    };
+    match work_second as f64 / goal_second as f64 {
+        x if x < 0.25 => 1,
+        x if x < 0.5 => 2,
+        x if x < 1.0 => 3,
+        _ => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, day).unwrap()
+    }
+
+    fn day(day: u32, work_minutes: u32) -> DayStat {
+        DayStat {
+            date: date(day),
+            work_second: work_minutes * 60,
+            break_second: 0,
+        }
+    }
+
+    #[test]
+    fn judge_compares_seconds_to_minutes() {
+        assert_eq!(judge(3600, 60, true), DayResult::Met);
+        assert_eq!(judge(3599, 60, true), DayResult::Missed);
+        assert_eq!(judge(1, 60, false), DayResult::Met);
+        assert_eq!(judge(0, 60, false), DayResult::Missed);
+        assert_eq!(judge(3600, 0, true), DayResult::Skipped);
+        assert_eq!(judge(3600, 0, false), DayResult::Met);
+    }
+
+    #[test]
+    fn missed_today_keeps_the_current_streak() {
+        let days = [day(4, 60), day(5, 90), day(6, 10)];
+        let streaks = streaks(&days, date(6), |_| 60, true);
+        assert_eq!(streaks.current, 2);
+        assert_eq!(streaks.longest, 2);
+        // once the day is over it breaks the streak
+        let streaks = super::streaks(&days, date(7), |_| 60, true);
+        assert_eq!(streaks.current, 0);
+        assert_eq!(streaks.longest, 2);
+    }
+
+    #[test]
+    fn days_without_a_goal_are_skipped() {
+        // the 5th is a day off and isn't recorded at all
+        let days = [day(4, 60), day(6, 60)];
+        let streaks = streaks(&days, date(6), |x| if x == date(5) { 0 } else { 60 }, true);
+        assert_eq!(streaks.current, 2);
+        assert_eq!(streaks.longest, 2);
+    }
+
+    #[test]
+    fn consistency_leaves_out_today() {
+        // the 4th of March 2024 is a monday
+        let days = [day(4, 60), day(11, 0)];
+        let consistency = weekday_consistency(&days, date(11), |_| 60, true);
+        assert_eq!(consistency[0], Some(1.0));
+        assert_eq!(consistency[1], Some(0.0));
+        let consistency = weekday_consistency(&days, date(12), |_| 60, true);
+        assert_eq!(consistency[0], Some(0.5));
+    }
+
+    #[test]
+    fn heat_level_uses_minutes() {
+        assert_eq!(heat_level(0, 60), 0);
+        assert_eq!(heat_level(10 * 60, 60), 1);
+        assert_eq!(heat_level(45 * 60, 60), 3);
+        assert_eq!(heat_level(60 * 60, 60), 4);
+        assert_eq!(heat_level(4 * 3600, 0), 4);
+    }
+}
@@ -0,0 +1,150 @@
+//! Built-in color themes, the light/dark preference and the user stylesheet.
+//!
+//! Themes only define the `flowtime_work`, `flowtime_break` and `flowtime_pause` colors,
+//! the widgets pick one of them through the `stage-*` class on the timer.
+//! `style.css` in the config directory is loaded on top and reloaded whenever it changes.
+use crate::{cfg, state, TimerMode};
+use gtk::gio;
+use gtk::prelude::*;
+use relm4::adw;
+use serde_derive::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::path::PathBuf;
+
+#[derive(PartialEq, Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// Follows the libadwaita accent color
+    #[default]
+    Adwaita,
+    Catppuccin,
+    Monochrome,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::Adwaita, Theme::Catppuccin, Theme::Monochrome];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Adwaita => "Adwaita",
+            Theme::Catppuccin => "Catppuccin",
+            Theme::Monochrome => "Monochrome",
+        }
+    }
+
+    /// Colors for work, break and pause
+    fn colors(self) -> [&'static str; 3] {
+        match self {
+            Theme::Adwaita => ["@accent_color", "@success_color", "@warning_color"],
+            Theme::Catppuccin => ["rgb(153,209,219)", "rgb(166,209,137)", "rgb(229,200,144)"],
+            Theme::Monochrome => [
+                "@window_fg_color",
+                "alpha(@window_fg_color, 0.7)",
+                "alpha(@window_fg_color, 0.4)",
+            ],
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorScheme {
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl ColorScheme {
+    pub const ALL: [ColorScheme; 3] = [ColorScheme::System, ColorScheme::Light, ColorScheme::Dark];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorScheme::System => "System",
+            ColorScheme::Light => "Light",
+            ColorScheme::Dark => "Dark",
+        }
+    }
+}
+
+/// CSS class of the timer for its current stage
+pub fn stage_class(mode: &TimerMode) -> &'static str {
+    match mode {
+        TimerMode::Clock | TimerMode::Stop => "stage-work",
+        TimerMode::CountDown => "stage-break",
+        TimerMode::Pause(_) => "stage-paused",
+    }
+}
+
+/// The user stylesheet, loaded after the built-in styles so it can override them
+pub fn user_css_path() -> PathBuf {
+    state::path("flowtime").with_file_name("style.css")
+}
+
+struct Providers {
+    theme: gtk::CssProvider,
+    // dropping the monitor stops it
+    _monitor: Option<gio::FileMonitor>,
+}
+
+thread_local! {
+    static PROVIDERS: RefCell<Option<Providers>> = RefCell::new(None);
+}
+
+fn load_user_css(provider: &gtk::CssProvider) {
+    match std::fs::read_to_string(user_css_path()) {
+        Ok(css) => provider.load_from_data(css.as_bytes()),
+        Err(_) => provider.load_from_data(b""),
+    }
+}
+
+/// Installs the style providers and the stylesheet monitor, has to run on the main thread
+pub fn init() {
+    let display = gtk::gdk::Display::default().unwrap();
+    let theme = gtk::CssProvider::new();
+    let user = gtk::CssProvider::new();
+    gtk::StyleContext::add_provider_for_display(
+        &display,
+        &theme,
+        gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+    );
+    gtk::StyleContext::add_provider_for_display(&display, &user, gtk::STYLE_PROVIDER_PRIORITY_USER);
+    load_user_css(&user);
+
+    let monitor = gio::File::for_path(user_css_path())
+        .monitor_file(gio::FileMonitorFlags::NONE, None::<&gio::Cancellable>)
+        .map_err(|e| eprintln!("flowtime: can't watch {}: {}", user_css_path().display(), e))
+        .ok();
+    if let Some(monitor) = &monitor {
+        monitor.connect_changed(move |_, _, _, _| load_user_css(&user));
+    }
+
+    PROVIDERS.with(|x| {
+        *x.borrow_mut() = Some(Providers {
+            theme,
+            _monitor: monitor,
+        })
+    });
+    apply();
+}
+
+/// Applies the theme and color scheme of the current config
+pub fn apply() {
+    let config = cfg!();
+    adw::StyleManager::default().set_color_scheme(match config.color_scheme {
+        ColorScheme::System => adw::ColorScheme::Default,
+        ColorScheme::Light => adw::ColorScheme::ForceLight,
+        ColorScheme::Dark => adw::ColorScheme::ForceDark,
+    });
+    let [work, breaks, pause] = config.theme.colors();
+    PROVIDERS.with(|x| {
+        if let Some(providers) = &*x.borrow() {
+            let css = format!(
+                "@define-color flowtime_work {work};\n\
+                 @define-color flowtime_break {breaks};\n\
+                 @define-color flowtime_pause {pause};\n"
+            );
+            providers.theme.load_from_data(css.as_bytes());
+        }
+    });
+}
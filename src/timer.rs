@@ -1,7 +1,17 @@
 pub use crate::time::Time;
-use crate::{cfg, stat, Config, Stats, CURRENT_MONTH};
+use crate::{
+    cfg, daily_goal, day_work, goal_progress, hour_minute, notify, profile, report_save, stat,
+    state, storage, theme, today, webhooks, week_work, DayStat, Interruption, Session, Stats,
+    CURRENT_MONTH,
+};
+use anyhow::Context;
+use chrono::{DateTime, Local};
 use std::time::Duration;
 
+use crate::hooks::{self, Event, Payload};
+use crate::overlay::{BreakOverlay, OverlayMsg, OverlayOutput};
+use crate::presence::{self, Presence};
+use crate::ring::ProgressRing;
 use gtk::prelude::*;
 use relm4::*;
 use rodio::Sink;
@@ -23,20 +33,70 @@ pub struct Timer {
     pub mode: TimerMode,
     pub time: Time,
     pub clicking: bool,
+    /// Whether the long session nudge was already sent for the current session
+    pub nudged: bool,
+    /// When the current work session started, `None` outside of work
+    pub session_start: Option<DateTime<Local>>,
+    /// What the user is working on, saved along with the session
+    pub tag: String,
+    /// Length of the current break when it started
+    pub break_total: u32,
+    /// When a postponed break continues
+    pub postponed_until: Option<DateTime<Local>>,
+    /// Whether the current break was postponed already, which is allowed once
+    pub postponed: bool,
+    /// Since when the user is idle, `None` while they are around
+    pub idle_since: Option<DateTime<Local>>,
+    /// When the user left, set while work is paused because they were idle
+    pub away_since: Option<DateTime<Local>>,
+    /// When the system went to sleep, `None` while it is awake
+    pub asleep_since: Option<DateTime<Local>>,
+    pub ring: ProgressRing,
+    pub overlay: Controller<BreakOverlay>,
 }
 impl Timer {
-    fn new() -> Timer {
+    fn new(overlay: Controller<BreakOverlay>) -> Timer {
         Timer {
             mode: TimerMode::Stop,
             time: Default::default(),
             clicking: false,
+            nudged: false,
+            session_start: None,
+            tag: String::new(),
+            break_total: 0,
+            postponed_until: None,
+            postponed: false,
+            idle_since: None,
+            away_since: None,
+            asleep_since: None,
+            ring: ProgressRing::new(260),
+            overlay,
         }
     }
     fn tick(&mut self) -> bool {
         match self.mode {
             TimerMode::Clock => {
+                if self.pause_if_idle() {
+                    return false;
+                }
+                let before = self.time.get_second();
                 self.time.increment_second();
-                false
+                self.check_goals(before);
+                let max_flow = cfg!().max_flow_minutes * 60;
+                if !self.nudged && max_flow != 0 && self.time.get_second() >= max_flow {
+                    self.nudged = true;
+                    notify(
+                        "nudge",
+                        "Time for a break?",
+                        &format!(
+                            "You have been working for {} minutes straight.",
+                            cfg!().max_flow_minutes
+                        ),
+                    );
+                    true
+                } else {
+                    false
+                }
             }
             TimerMode::CountDown => {
                 if self.time.second == 0
@@ -46,8 +106,12 @@ impl Timer {
                 {
                     if cfg!().restart {
                         self.mode = TimerMode::Clock;
+                        self.session_start = Some(Local::now());
+                        self.hook(Event::BreakEnded);
+                        self.hook(Event::WorkStarted);
                     } else {
                         self.mode = TimerMode::Stop;
+                        self.hook(Event::BreakEnded);
                     }
                     true
                 } else {
@@ -56,13 +120,189 @@ impl Timer {
                 }
             }
             TimerMode::Stop => false,
-            TimerMode::Pause(_) => false,
+            TimerMode::Pause(_) => {
+                if self.postponed_until.map_or(false, |x| x <= Local::now()) {
+                    self.postponed_until = None;
+                    self.mode = TimerMode::CountDown;
+                }
+                false
+            }
+        }
+    }
+
+    /// Pauses work once the user was idle for `idle_minutes`, taking the idle time back off
+    /// the clock until they say how it counts
+    fn pause_if_idle(&mut self) -> bool {
+        let limit = cfg!().idle_minutes * 60;
+        let since = match (self.idle_since, self.session_start) {
+            (Some(idle), Some(start)) if limit != 0 => idle.max(start),
+            _ => return false,
+        };
+        let idle = (Local::now() - since).num_seconds().max(0) as u32;
+        if idle < limit {
+            return false;
+        }
+        self.time
+            .set_time_by_second(self.time.get_second().saturating_sub(idle));
+        self.mode = TimerMode::Pause(Box::from(TimerMode::Clock));
+        self.away_since = Some(since);
+        self.hook(Event::WorkPaused);
+        notify(
+            "idle",
+            "Paused while you are away",
+            &format!("Nobody was around since {}.", since.format("%H:%M")),
+        );
+        true
+    }
+
+    /// Ends the work session and starts the break it earned
+    fn take_break(&mut self) {
+        // only a session that actually ran can be a fragmented one
+        if self.session_work() > 0 {
+            report_save(update_fragmented(self.time.get_second()));
+        }
+        self.mode = TimerMode::CountDown;
+        self.nudged = false;
+        report_save(update_statistics(self, None));
+        let work_second = self.time.get_second();
+        report_save(update_today(|day| {
+            day.work_second += work_second;
+            day.break_second += work_second / cfg!().break_ratio;
+        }));
+        report_save(record_session(self, work_second / cfg!().break_ratio));
+        self.session_start = None;
+        self.time
+            .set_time_by_second(self.time.get_second() / cfg!().break_ratio);
+        self.break_total = self.time.get_second();
+        self.postponed = false;
+        self.hook(Event::BreakStarted);
+    }
+
+    /// Seconds until a break in strict mode can be ended, paused or postponed, 0 otherwise
+    fn strict_left(&self) -> u32 {
+        let config = cfg!();
+        if !config.break_overlay || self.mode != TimerMode::CountDown {
+            return 0;
+        }
+        config
+            .strict_break_seconds
+            .saturating_sub(self.break_total.saturating_sub(self.time.get_second()))
+    }
+
+    /// Runs the user's hook and sends the webhooks for `event` with the current state
+    fn hook(&self, event: Event) {
+        publish(&Payload::new(
+            event,
+            &self.mode,
+            self.time.get_second(),
+            &self.tag,
+        ));
+    }
+
+    /// Asks how the time since `since` counts, after work paused because the user was away
+    fn ask_away(&self, since: DateTime<Local>, root: &gtk::Box, sender: &ComponentSender<Self>) {
+        let away = (Local::now() - since).num_seconds().max(0) as u32;
+        let dialog = gtk::MessageDialog::builder()
+            .modal(true)
+            .text("Welcome back")
+            .secondary_text(
+                format!(
+                    "Work paused while you were away for {}. How should that time count?",
+                    hour_minute(away)
+                )
+                .as_str(),
+            )
+            .build();
+        if let Some(window) = root.root().and_then(|x| x.downcast::<gtk::Window>().ok()) {
+            dialog.set_transient_for(Some(&window));
+        }
+        dialog.add_buttons(&[
+            ("Discard", gtk::ResponseType::Reject),
+            ("Break", gtk::ResponseType::Other(1)),
+            ("Work", gtk::ResponseType::Accept),
+        ]);
+        let sender = sender.clone();
+        dialog.connect_response(move |dialog, response| {
+            match response {
+                gtk::ResponseType::Accept => sender.input(TimerMsg::Away(Away::Work, away)),
+                gtk::ResponseType::Other(1) => sender.input(TimerMsg::Away(Away::Break, away)),
+                // stays paused without the time
+                _ => (),
+            }
+            dialog.destroy();
+        });
+        dialog.show();
+    }
+
+    /// Sends a notification when the current session crosses the daily or weekly goal
+    fn check_goals(&self, before: u32) {
+        let after = self.time.get_second();
+        let goal = daily_goal(today()) * 60;
+        let saved = day_work(today());
+        if goal != 0 && saved + before < goal && saved + after >= goal {
+            notify(
+                "goal",
+                "Daily goal reached!",
+                &format!("You have focused for {} today.", hour_minute(saved + after)),
+            );
+            publish(&Payload {
+                goal: Some("daily"),
+                ..Payload::new(Event::GoalReached, &self.mode, after, &self.tag)
+            });
+        }
+        let goal = cfg!().weekly_goal_minutes * 60;
+        let saved = week_work(today());
+        if goal != 0 && saved + before < goal && saved + after >= goal {
+            notify(
+                "goal",
+                "Weekly goal reached!",
+                &format!(
+                    "You have focused for {} this week.",
+                    hour_minute(saved + after)
+                ),
+            );
+            publish(&Payload {
+                goal: Some("weekly"),
+                ..Payload::new(Event::GoalReached, &self.mode, after, &self.tag)
+            });
+        }
+    }
+
+    /// Work time of the running session that has not been saved to statistics yet
+    fn session_work(&self) -> u32 {
+        match &self.mode {
+            TimerMode::Clock => self.time.get_second(),
+            TimerMode::Pause(x) if **x == TimerMode::Clock => self.time.get_second(),
+            _ => 0,
         }
     }
 
     fn formatted_string(&self) -> String {
         self.time.formatted_string()
     }
+
+    /// How far the ring around the clock is filled in `mode`
+    fn ring_fraction(&self, mode: &TimerMode) -> f64 {
+        let second = self.time.get_second();
+        match mode {
+            TimerMode::CountDown if self.break_total != 0 => {
+                second as f64 / self.break_total as f64
+            }
+            // work fills up towards the fragmented threshold first, then towards the nudge
+            TimerMode::Clock => {
+                let config = cfg!();
+                [config.min_focus_minutes, config.max_flow_minutes]
+                    .into_iter()
+                    .map(|x| x * 60)
+                    .filter(|x| *x > second)
+                    .min()
+                    .map(|next| second as f64 / next as f64)
+                    .unwrap_or(1.0)
+            }
+            TimerMode::Pause(x) => self.ring_fraction(x),
+            _ => 0.0,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -70,6 +310,64 @@ pub enum TimerMsg {
     ToggleFlowTime,
     ToggleBreak,
     ResetSession,
+    SetTag(String),
+    /// Records that something broke the focus, from a global hotkey
+    LogInterruption,
+    /// Ends the break right away, from the break overlay
+    SkipBreak,
+    /// Holds the break for `postpone_minutes`, from the break overlay
+    PostponeBreak,
+    /// Redraws the view after the config or statistics changed on disk
+    Refresh,
+    /// The user went idle or came back
+    Presence(Presence),
+    /// How the given seconds away from the computer count
+    Away(Away, u32),
+}
+
+#[derive(Debug)]
+pub enum Away {
+    Work,
+    /// Starts the earned break with the time away already taken off
+    Break,
+}
+
+#[derive(Debug)]
+pub enum TimerOutput {
+    /// Sent after every update, for views of the timer outside of its own
+    Changed {
+        mode: TimerMode,
+        time: String,
+        second: u32,
+        /// Work of the running session that isn't saved yet
+        session_work: u32,
+        fraction: f64,
+    },
+}
+
+/// Name of the stage for scripts and other apps: `work`, `break`, `paused` or `stopped`
+pub fn mode_key(mode: &TimerMode) -> &'static str {
+    match mode {
+        TimerMode::Clock => "work",
+        TimerMode::CountDown => "break",
+        TimerMode::Pause(_) => "paused",
+        TimerMode::Stop => "stopped",
+    }
+}
+
+/// Label of the stage the timer is in, empty when stopped
+pub fn stage_name(mode: &TimerMode) -> &'static str {
+    match mode {
+        TimerMode::Clock => "Working Stage",
+        TimerMode::CountDown => "Free Time!",
+        TimerMode::Stop => "",
+        TimerMode::Pause(x) => match **x {
+            TimerMode::Clock => "Working Stage",
+            TimerMode::CountDown => "Free Time!",
+            TimerMode::Stop => "",
+            TimerMode::Pause(_) => "",
+        },
+    }
 }
 
 #[derive(Debug)]
@@ -78,26 +376,79 @@ pub enum CommandMsg {
     Empty,
 }
 
-fn update_statistics(timer: &mut Timer, save_time: Option<(u32, u32)>) {
+fn publish(payload: &Payload) {
+    hooks::run(payload);
+    webhooks::send(payload);
+}
+
+/// Plays the configured sound to its end
+fn play_sound() -> anyhow::Result<()> {
+    let (_stream, stream_handle) = OutputStream::try_default()?;
+    let path = cfg!().sound();
+    let file = File::open(&path).with_context(|| format!("can't open {}", path.display()))?;
+    let source = Decoder::new(BufReader::new(file))
+        .with_context(|| format!("can't decode {}", path.display()))?;
+    let sink = Sink::try_new(&stream_handle)?;
+    sink.append(source);
+    sink.sleep_until_end();
+    Ok(())
+}
+
+fn update_fragmented(work_second: u32) -> anyhow::Result<()> {
+    let min_focus = cfg!().min_focus_minutes * 60;
+    if min_focus == 0 || work_second >= min_focus {
+        return Ok(());
+    }
+    let mut stats = stat!();
+    match stats
+        .month_fragmented
+        .iter_mut()
+        .find(|(month, _)| month == &*CURRENT_MONTH)
+    {
+        Some((_, count)) => *count += 1,
+        None => stats.month_fragmented.push((*CURRENT_MONTH, 1)),
+    }
+    state::save_stats(stats)
+}
+
+fn update_today(f: impl FnOnce(&mut DayStat)) -> anyhow::Result<()> {
+    storage::update_day(today(), f)
+}
+
+fn record_session(timer: &Timer, break_second: u32) -> anyhow::Result<()> {
+    let work_second = timer.time.get_second();
+    match (timer.session_start, work_second != 0) {
+        (Some(start), true) => storage::add_session(Session {
+            start,
+            end: Local::now(),
+            work_second,
+            break_second,
+            tag: timer.tag.clone(),
+            profile: profile::active(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn update_statistics(timer: &mut Timer, save_time: Option<(u32, u32)>) -> anyhow::Result<()> {
     let stats = stat!();
     for (conf_month, break_second, work_second) in stats.month_break_work.iter() {
         if &*CURRENT_MONTH == conf_month {
-            confy::store(
-                "flowtime",
-                Some("statistics"),
-                Stats {
-                    month_break_work: vec![(
-                        *conf_month,
-                        save_time
-                            .unwrap_or((break_second + timer.time.get_second() / 5, 0))
-                            .0,
-                        save_time
-                            .unwrap_or((0, work_second + timer.time.get_second()))
-                            .1,
-                    )],
-                },
-            )
-            .unwrap();
+            state::save_stats(Stats {
+                month_break_work: vec![(
+                    *conf_month,
+                    save_time
+                        .unwrap_or((
+                            break_second + timer.time.get_second() / cfg!().break_ratio,
+                            0,
+                        ))
+                        .0,
+                    save_time
+                        .unwrap_or((0, work_second + timer.time.get_second()))
+                        .1,
+                )],
+                ..stat!()
+            })?;
         } else if !stats
             .month_break_work
             .iter()
@@ -107,29 +458,29 @@ fn update_statistics(timer: &mut Timer, save_time: Option<(u32, u32)>) {
             new_struct.push((
                 *CURRENT_MONTH,
                 save_time
-                    .unwrap_or((break_second + timer.time.get_second() / 5, 0))
+                    .unwrap_or((
+                        break_second + timer.time.get_second() / cfg!().break_ratio,
+                        0,
+                    ))
                     .0,
                 save_time
                     .unwrap_or((0, work_second + timer.time.get_second()))
                     .1,
             ));
-            confy::store(
-                "flowtime",
-                Some("statistics"),
-                Stats {
-                    month_break_work: new_struct,
-                },
-            )
-            .unwrap();
+            state::save_stats(Stats {
+                month_break_work: new_struct,
+                ..stat!()
+            })?;
         }
     }
+    Ok(())
 }
 
 #[relm4::component(pub)]
 impl Component for Timer {
     type Init = TimerMode;
     type Input = TimerMsg;
-    type Output = ();
+    type Output = TimerOutput;
     type CommandOutput = CommandMsg;
 
     view! {
@@ -137,30 +488,47 @@ impl Component for Timer {
             set_orientation: gtk::Orientation::Vertical,
             set_valign: gtk::Align::Center,
             set_spacing: 10,
+            #[watch]
+            set_css_classes: &[theme::stage_class(&model.mode)],
 
+            #[local_ref]
+            clock_ring -> gtk::Overlay {
+                add_overlay = &gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_valign: gtk::Align::Center,
+                    set_spacing: 10,
 
-            gtk::Label {
-                add_css_class: "mode",
-                #[watch]
-                set_visible: !matches!(&model.mode, TimerMode::Stop),
-                #[watch]
-                set_label: match &model.mode {
-                    TimerMode::Clock => "Working Stage",
-                    TimerMode::CountDown => "Free Time!",
-                    TimerMode::Stop => "",
-                    TimerMode::Pause(x) => match **x {
-                        TimerMode::Clock => "Working Stage",
-                        TimerMode::CountDown => "Free Time!",
-                        TimerMode::Stop => "",
-                        TimerMode::Pause(_) => "",
+                    gtk::Label {
+                        add_css_class: "mode",
+                        #[watch]
+                        set_visible: !matches!(&model.mode, TimerMode::Stop),
+                        #[watch]
+                        set_label: stage_name(&model.mode),
+                    },
+
+                    gtk::Label {
+                        add_css_class: "clock",
+                        #[watch]
+                        set_label: &model.formatted_string(),
+                    },
+                    gtk::Label {
+                        add_css_class: "goal",
+                        #[watch]
+                        set_visible: daily_goal(today()) != 0,
+                        #[watch]
+                        set_label: &goal_progress(
+                            day_work(today()) + model.session_work(),
+                            daily_goal(today())
+                        ),
                     },
                 },
             },
-
-            gtk::Label {
-                add_css_class: "clock",
-                #[watch]
-                set_label: &model.formatted_string(),
+            gtk::Entry {
+                set_placeholder_text: Some("What are you working on?"),
+                set_halign: gtk::Align::Center,
+                connect_changed[sender] => move |entry| {
+                    sender.input(TimerMsg::SetTag(entry.text().to_string()));
+                },
             },
             gtk::Box {
                 set_orientation: gtk::Orientation::Horizontal,
@@ -200,12 +568,20 @@ impl Component for Timer {
         root: &Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        let model = Timer::new();
+        let overlay = BreakOverlay::builder()
+            .launch(())
+            .forward(sender.input_sender(), |msg| match msg {
+                OverlayOutput::Skip => TimerMsg::SkipBreak,
+                OverlayOutput::Postpone => TimerMsg::PostponeBreak,
+            });
+        let model = Timer::new(overlay);
+        let presence_sender = sender.clone();
+        presence::watch(move |x| presence_sender.input(TimerMsg::Presence(x)));
+        let clock_ring = model.ring.widget();
         relm4::set_global_css(
             r#"
             .clock {
                 font-size: 40px;
-                color: rgb(153,209,219);
             }
             .reset {
                 font-size: 25px;
@@ -216,15 +592,25 @@ impl Component for Timer {
             }
             .mode {
                 font-size: 15px;
-                color: rgb(153,209,219);
             }
             .circular {
-                color: rgb(153,209,219);
                 padding: 10px;
             }
             .break {
                 font-size: 20px;
             }
+            .goal {
+                font-size: 13px;
+            }
+            .stage-work .clock, .stage-work .mode, .stage-work .circular, .stage-work .goal, .stage-work .ring {
+                color: @flowtime_work;
+            }
+            .stage-break .clock, .stage-break .mode, .stage-break .circular, .stage-break .goal, .stage-break .ring {
+                color: @flowtime_break;
+            }
+            .stage-paused .clock, .stage-paused .mode, .stage-paused .circular, .stage-paused .goal, .stage-paused .ring {
+                color: @flowtime_pause;
+            }
 "#,
         );
 
@@ -234,27 +620,45 @@ impl Component for Timer {
         ComponentParts { model, widgets }
     }
 
-    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>, _: &Self::Root) {
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>, root: &Self::Root) {
+        // every way out of a strict break is closed until it ran long enough, wherever it comes from
+        if self.strict_left() != 0
+            && matches!(
+                msg,
+                TimerMsg::ToggleFlowTime
+                    | TimerMsg::ResetSession
+                    | TimerMsg::SkipBreak
+                    | TimerMsg::PostponeBreak
+            )
+        {
+            return;
+        }
         match msg {
             TimerMsg::ToggleBreak => match &self.mode {
-                TimerMode::Clock | TimerMode::Pause(_) | TimerMode::Stop => {
+                TimerMode::Clock | TimerMode::Stop => self.take_break(),
+                TimerMode::Pause(x) if **x == TimerMode::Clock => self.take_break(),
+                // a paused or postponed break is already earned, asking for it resumes it
+                TimerMode::Pause(_) => {
                     self.mode = TimerMode::CountDown;
-                    update_statistics(self, None);
-                    self.time.set_time_by_second(self.time.get_second() / 5);
+                    self.postponed_until = None;
                 }
-                _ => (),
+                TimerMode::CountDown => (),
             },
             TimerMsg::ToggleFlowTime => match &self.mode {
                 TimerMode::Stop => {
                     self.mode = TimerMode::Clock;
+                    self.nudged = false;
+                    self.session_start = Some(Local::now());
                     self.time.reset_time();
                     if !self.clicking {
                         sender.spawn_oneshot_command(|| CommandMsg::Tick);
                         self.clicking = true;
                     }
+                    self.hook(Event::WorkStarted);
                 }
                 TimerMode::Clock => {
                     self.mode = TimerMode::Pause(Box::from(TimerMode::Clock));
+                    self.hook(Event::WorkPaused);
                 }
                 TimerMode::CountDown => {
                     self.mode = TimerMode::Pause(Box::from(TimerMode::CountDown));
@@ -262,9 +666,11 @@ impl Component for Timer {
                 TimerMode::Pause(x) => match **x {
                     TimerMode::Clock => {
                         self.mode = TimerMode::Clock;
+                        self.hook(Event::WorkStarted);
                     }
                     TimerMode::CountDown => {
                         self.mode = TimerMode::CountDown;
+                        self.postponed_until = None;
                     }
                     _ => unreachable!(),
                 },
@@ -275,23 +681,127 @@ impl Component for Timer {
                         if self.mode == TimerMode::CountDown {
                             self.mode = TimerMode::Clock;
                             if cfg!().reset_save {
-                                update_statistics(
+                                report_save(update_statistics(
                                     self,
                                     Some((break_time - self.time.second, *work)),
-                                );
+                                ));
+                                let unused_break = self.time.get_second();
+                                report_save(update_today(|day| {
+                                    day.break_second =
+                                        day.break_second.saturating_sub(unused_break);
+                                }));
                             }
                         } else if self.mode == TimerMode::Clock {
                             if cfg!().reset_save {
-                                update_statistics(
+                                report_save(update_statistics(
                                     self,
                                     Some((*break_time, work + self.time.second)),
-                                );
+                                ));
+                                report_save(update_fragmented(self.time.get_second()));
+                                let work_second = self.time.get_second();
+                                report_save(update_today(|day| day.work_second += work_second));
+                                report_save(record_session(self, 0));
                             }
                         }
                     }
                 }
+                self.nudged = false;
+                self.session_start = match &self.mode {
+                    TimerMode::Clock => Some(Local::now()),
+                    TimerMode::Pause(x) if **x == TimerMode::Clock => Some(Local::now()),
+                    _ => None,
+                };
                 self.time.reset_time();
+                self.hook(Event::SessionReset);
             }
+            TimerMsg::SetTag(tag) => {
+                self.tag = tag;
+            }
+            TimerMsg::LogInterruption => {
+                let time = Local::now();
+                report_save(storage::add_interruption(Interruption {
+                    time,
+                    note: self.tag.clone(),
+                }));
+                notify(
+                    "interruption",
+                    "Interruption logged",
+                    &format!("At {}", time.format("%H:%M")),
+                );
+            }
+            // the earned break stays in the statistics, the next tick ends it like any other
+            TimerMsg::SkipBreak => {
+                if self.mode == TimerMode::CountDown {
+                    self.time.reset_time();
+                }
+            }
+            TimerMsg::PostponeBreak => {
+                if self.mode == TimerMode::CountDown
+                    && !self.postponed
+                    && cfg!().postpone_minutes != 0
+                {
+                    self.postponed = true;
+                    self.mode = TimerMode::Pause(Box::from(TimerMode::CountDown));
+                    self.postponed_until = Some(
+                        Local::now() + chrono::Duration::minutes(cfg!().postpone_minutes as i64),
+                    );
+                }
+            }
+            TimerMsg::Refresh => (),
+            TimerMsg::Presence(Presence::Idle(since)) => {
+                // the logind and screensaver signals can both arrive, the first one counts
+                self.idle_since.get_or_insert(since);
+            }
+            TimerMsg::Presence(Presence::Active) => {
+                self.idle_since = None;
+                if let Some(since) = self.away_since.take() {
+                    if self.mode == TimerMode::Pause(Box::from(TimerMode::Clock)) {
+                        self.ask_away(since, root, &sender);
+                    }
+                }
+            }
+            TimerMsg::Presence(Presence::Locked) => {
+                if self.mode == TimerMode::Clock && cfg!().break_on_lock {
+                    self.take_break();
+                }
+            }
+            TimerMsg::Presence(Presence::Sleeping(since, delay)) => {
+                self.asleep_since = Some(since);
+                if self.mode == TimerMode::Clock {
+                    self.mode = TimerMode::Pause(Box::from(TimerMode::Clock));
+                    self.hook(Event::WorkPaused);
+                }
+                // the pause is taken, the system may suspend now
+                drop(delay);
+            }
+            // the ticker stands still during suspend, but a break went on meanwhile
+            TimerMsg::Presence(Presence::Resumed) => {
+                if let Some(since) = self.asleep_since.take() {
+                    if self.mode == TimerMode::CountDown {
+                        let slept = (Local::now() - since).num_seconds().max(0) as u32;
+                        self.time
+                            .set_time_by_second(self.time.get_second().saturating_sub(slept));
+                    }
+                }
+            }
+            TimerMsg::Away(away, second)
+                if self.mode == TimerMode::Pause(Box::from(TimerMode::Clock)) =>
+            {
+                match away {
+                    Away::Work => {
+                        self.time
+                            .set_time_by_second(self.time.get_second() + second);
+                        self.mode = TimerMode::Clock;
+                        self.hook(Event::WorkStarted);
+                    }
+                    Away::Break => {
+                        self.take_break();
+                        self.time
+                            .set_time_by_second(self.time.get_second().saturating_sub(second));
+                    }
+                }
+            }
+            TimerMsg::Away(..) => (),
         }
     }
     fn update_cmd(
@@ -302,16 +812,11 @@ impl Component for Timer {
     ) {
         if let CommandMsg::Tick = message {
             // ticking logic handled by Timer
-            if self.tick() {
+            if self.tick() && cfg!().sounds {
                 sender.spawn_oneshot_command(move || {
-                    let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-                    let file: BufReader<std::fs::File> =
-                        BufReader::new(File::open("tone.wav").unwrap());
-                    let source = Decoder::new(file).unwrap();
-                    // Create a sink to play the audio
-                    let sink = Sink::try_new(&stream_handle).unwrap();
-                    sink.append(source);
-                    sink.sleep_until_end();
+                    if let Err(e) = play_sound() {
+                        eprintln!("flowtime: failed to play the sound: {:#}", e);
+                    }
                     CommandMsg::Empty
                 });
             }
@@ -321,4 +826,25 @@ impl Component for Timer {
             });
         };
     }
+
+    fn post_view() {
+        let fraction = model.ring_fraction(&model.mode);
+        model.ring.set_fraction(fraction);
+        let config = cfg!();
+        model.overlay.emit(OverlayMsg::Update {
+            visible: config.break_overlay && model.mode == TimerMode::CountDown,
+            time: model.formatted_string(),
+            skip_in: model.strict_left(),
+            can_postpone: !model.postponed,
+        });
+        sender
+            .output(TimerOutput::Changed {
+                mode: model.mode.clone(),
+                time: model.formatted_string(),
+                second: model.time.get_second(),
+                session_work: model.session_work(),
+                fraction,
+            })
+            .ok();
+    }
 }
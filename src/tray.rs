@@ -0,0 +1,380 @@
+//! Tray icon over the StatusNotifierItem protocol, with its menu over `com.canonical.dbusmenu`.
+//!
+//! The icon shows the stage and the tooltip the time, both are pushed by `update` on every
+//! tick. The item registers itself again whenever a `StatusNotifierWatcher` (re)appears.
+use crate::timer::{stage_name, TimerMode};
+use gtk::gio;
+use gtk::glib::thread_guard::ThreadGuard;
+use gtk::glib::{ToVariant, Variant};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Mutex;
+
+const ITEM_PATH: &str = "/StatusNotifierItem";
+const MENU_PATH: &str = "/MenuBar";
+const ITEM: &str = "org.kde.StatusNotifierItem";
+const MENU: &str = "com.canonical.dbusmenu";
+const WATCHER: &str = "org.kde.StatusNotifierWatcher";
+
+const INTERFACES: &str = r#"
+<node>
+  <interface name="org.kde.StatusNotifierItem">
+    <property name="Category" type="s" access="read"/>
+    <property name="Id" type="s" access="read"/>
+    <property name="Title" type="s" access="read"/>
+    <property name="Status" type="s" access="read"/>
+    <property name="IconName" type="s" access="read"/>
+    <property name="ToolTip" type="(sa(iiay)ss)" access="read"/>
+    <property name="ItemIsMenu" type="b" access="read"/>
+    <property name="Menu" type="o" access="read"/>
+    <method name="Activate"><arg type="i" direction="in"/><arg type="i" direction="in"/></method>
+    <method name="SecondaryActivate"><arg type="i" direction="in"/><arg type="i" direction="in"/></method>
+    <method name="ContextMenu"><arg type="i" direction="in"/><arg type="i" direction="in"/></method>
+    <method name="Scroll"><arg type="i" direction="in"/><arg type="s" direction="in"/></method>
+    <signal name="NewIcon"/>
+    <signal name="NewToolTip"/>
+    <signal name="NewStatus"><arg type="s"/></signal>
+  </interface>
+  <interface name="com.canonical.dbusmenu">
+    <property name="Version" type="u" access="read"/>
+    <property name="TextDirection" type="s" access="read"/>
+    <property name="Status" type="s" access="read"/>
+    <property name="IconThemePath" type="as" access="read"/>
+    <method name="GetLayout">
+      <arg type="i" direction="in"/><arg type="i" direction="in"/><arg type="as" direction="in"/>
+      <arg type="u" direction="out"/><arg type="(ia{sv}av)" direction="out"/>
+    </method>
+    <method name="GetGroupProperties">
+      <arg type="ai" direction="in"/><arg type="as" direction="in"/>
+      <arg type="a(ia{sv})" direction="out"/>
+    </method>
+    <method name="GetProperty">
+      <arg type="i" direction="in"/><arg type="s" direction="in"/><arg type="v" direction="out"/>
+    </method>
+    <method name="Event">
+      <arg type="i" direction="in"/><arg type="s" direction="in"/>
+      <arg type="v" direction="in"/><arg type="u" direction="in"/>
+    </method>
+    <method name="EventGroup">
+      <arg type="a(isvu)" direction="in"/><arg type="ai" direction="out"/>
+    </method>
+    <method name="AboutToShow"><arg type="i" direction="in"/><arg type="b" direction="out"/></method>
+    <method name="AboutToShowGroup">
+      <arg type="ai" direction="in"/><arg type="ai" direction="out"/><arg type="ai" direction="out"/>
+    </method>
+    <signal name="LayoutUpdated"><arg type="u"/><arg type="i"/></signal>
+    <signal name="ItemsPropertiesUpdated">
+      <arg type="a(ia{sv})"/><arg type="a(ias)"/>
+    </signal>
+  </interface>
+</node>
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrayAction {
+    Toggle,
+    Break,
+    Reset,
+    Show,
+    Quit,
+}
+
+/// Menu item ids, 0 is the root
+const ACTIONS: [(i32, TrayAction); 5] = [
+    (1, TrayAction::Toggle),
+    (2, TrayAction::Break),
+    (3, TrayAction::Reset),
+    (4, TrayAction::Show),
+    (5, TrayAction::Quit),
+];
+
+struct State {
+    connection: Option<gio::DBusConnection>,
+    mode: TimerMode,
+    time: String,
+    /// Bumped whenever a menu label changes
+    revision: u32,
+}
+
+/// Handle to the tray icon, cheap to clone
+#[derive(Clone)]
+pub struct Tray {
+    state: Rc<RefCell<State>>,
+    activate: Rc<dyn Fn(TrayAction)>,
+}
+
+fn icon(mode: &TimerMode) -> &'static str {
+    match mode {
+        TimerMode::Stop => "alarm-symbolic",
+        TimerMode::Clock => "media-record-symbolic",
+        TimerMode::CountDown => "face-smile-symbolic",
+        TimerMode::Pause(_) => "media-playback-pause-symbolic",
+    }
+}
+
+fn toggle_label(mode: &TimerMode) -> &'static str {
+    match mode {
+        TimerMode::Stop => "Start",
+        TimerMode::Pause(_) => "Resume",
+        _ => "Pause",
+    }
+}
+
+impl Tray {
+    /// Starts exporting the icon, `activate` gets the menu items and clicks on the icon
+    pub fn new(activate: impl Fn(TrayAction) + 'static) -> Tray {
+        let tray = Tray {
+            state: Rc::new(RefCell::new(State {
+                connection: None,
+                mode: TimerMode::Stop,
+                time: String::new(),
+                revision: 1,
+            })),
+            activate: Rc::new(activate),
+        };
+        let started = tray.clone();
+        gio::bus_get(
+            gio::BusType::Session,
+            None::<&gio::Cancellable>,
+            move |connection| match connection {
+                Ok(connection) => started.export(connection),
+                Err(e) => eprintln!("flowtime: no session bus for the tray icon: {}", e),
+            },
+        );
+        tray
+    }
+
+    fn export(&self, connection: gio::DBusConnection) {
+        let node = gio::DBusNodeInfo::for_xml(INTERFACES).unwrap();
+        for (path, name) in [(ITEM_PATH, ITEM), (MENU_PATH, MENU)] {
+            let interface = node.lookup_interface(name).unwrap();
+            // gio wants thread safe handlers, though it calls them on this thread only
+            let calls = Mutex::new(ThreadGuard::new(self.clone()));
+            let properties = Mutex::new(ThreadGuard::new(self.clone()));
+            let registered = connection.register_object(
+                path,
+                &interface,
+                move |_, _, _, interface, method, parameters, invocation| {
+                    let calls = calls.lock().unwrap();
+                    calls
+                        .get_ref()
+                        .call(interface, method, &parameters, invocation)
+                },
+                move |_, _, _, interface, property| {
+                    let properties = properties.lock().unwrap();
+                    // GDBus answers properties missing from `INTERFACES` itself, and a getter
+                    // can't fail here, so this is only reached when the two disagree
+                    properties
+                        .get_ref()
+                        .property(interface, property)
+                        .unwrap_or_else(|| {
+                            eprintln!("flowtime: unknown tray property {}.{}", interface, property);
+                            ().to_variant()
+                        })
+                },
+                |_, _, _, _, _, _| false,
+            );
+            if let Err(e) = registered {
+                eprintln!("flowtime: failed to export the tray icon: {}", e);
+                return;
+            }
+        }
+        self.state.borrow_mut().connection = Some(connection.clone());
+
+        // the watcher only keeps items registered while it runs
+        let name = connection
+            .unique_name()
+            .map(|x| x.to_string())
+            .unwrap_or_default();
+        gio::bus_watch_name_on_connection(
+            &connection,
+            WATCHER,
+            gio::BusNameWatcherFlags::NONE,
+            move |connection, _, _| {
+                connection.call(
+                    Some(WATCHER),
+                    "/StatusNotifierWatcher",
+                    WATCHER,
+                    "RegisterStatusNotifierItem",
+                    Some(&(name.as_str(),).to_variant()),
+                    None,
+                    gio::DBusCallFlags::NONE,
+                    -1,
+                    None::<&gio::Cancellable>,
+                    |result| {
+                        if let Err(e) = result {
+                            eprintln!("flowtime: failed to register the tray icon: {}", e);
+                        }
+                    },
+                )
+            },
+            |_, _| (),
+        );
+    }
+
+    /// Shows the current state of the timer
+    pub fn update(&self, mode: &TimerMode, time: &str) {
+        let mut state = self.state.borrow_mut();
+        let icon_changed = icon(&state.mode) != icon(mode);
+        let labels_changed = toggle_label(&state.mode) != toggle_label(mode)
+            || (state.mode == TimerMode::CountDown) != (*mode == TimerMode::CountDown);
+        state.mode = mode.clone();
+        state.time = time.to_string();
+        if labels_changed {
+            state.revision += 1;
+        }
+        let connection = match &state.connection {
+            Some(x) => x.clone(),
+            None => return,
+        };
+        let revision = state.revision;
+        drop(state);
+
+        let emit = |path: &str, interface: &str, signal: &str, parameters: Option<Variant>| {
+            connection
+                .emit_signal(None, path, interface, signal, parameters.as_ref())
+                .ok();
+        };
+        emit(ITEM_PATH, ITEM, "NewToolTip", None);
+        if icon_changed {
+            emit(ITEM_PATH, ITEM, "NewIcon", None);
+        }
+        if labels_changed {
+            emit(
+                MENU_PATH,
+                MENU,
+                "LayoutUpdated",
+                Some((revision, 0i32).to_variant()),
+            );
+        }
+    }
+
+    fn property(&self, interface: &str, property: &str) -> Option<Variant> {
+        let state = self.state.borrow();
+        Some(match (interface, property) {
+            (ITEM, "Category") => "ApplicationStatus".to_variant(),
+            (ITEM, "Id") => "flowtime".to_variant(),
+            (ITEM, "Title") => "Flowtime".to_variant(),
+            (ITEM, "Status") => "Active".to_variant(),
+            (ITEM, "IconName") => icon(&state.mode).to_variant(),
+            (ITEM, "ToolTip") => {
+                let title = match &state.mode {
+                    TimerMode::Stop => "Flowtime",
+                    TimerMode::Pause(_) => "Paused",
+                    x => stage_name(x),
+                };
+                let pixmaps: Vec<(i32, i32, Vec<u8>)> = vec![];
+                (icon(&state.mode), pixmaps, title, state.time.as_str()).to_variant()
+            }
+            (ITEM, "ItemIsMenu") => false.to_variant(),
+            (ITEM, "Menu") => gtk::glib::variant::ObjectPath::try_from(MENU_PATH.to_string())
+                .unwrap()
+                .to_variant(),
+            (MENU, "Version") => 3u32.to_variant(),
+            (MENU, "TextDirection") => "ltr".to_variant(),
+            (MENU, "Status") => "normal".to_variant(),
+            (MENU, "IconThemePath") => Vec::<String>::new().to_variant(),
+            _ => return None,
+        })
+    }
+
+    fn item_properties(&self, id: i32) -> HashMap<String, Variant> {
+        let mode = self.state.borrow().mode.clone();
+        let (label, enabled) = match ACTIONS.iter().find(|(x, _)| *x == id) {
+            Some((_, TrayAction::Toggle)) => (toggle_label(&mode), true),
+            Some((_, TrayAction::Break)) => ("Take a break", mode != TimerMode::CountDown),
+            Some((_, TrayAction::Reset)) => ("Reset", true),
+            Some((_, TrayAction::Show)) => ("Show Flowtime", true),
+            Some((_, TrayAction::Quit)) => ("Quit", true),
+            None => {
+                return HashMap::from([("children-display".to_string(), "submenu".to_variant())])
+            }
+        };
+        HashMap::from([
+            ("label".to_string(), label.to_variant()),
+            ("enabled".to_string(), enabled.to_variant()),
+        ])
+    }
+
+    fn layout(&self) -> Variant {
+        let children: Vec<Variant> = ACTIONS
+            .iter()
+            .map(|(id, _)| {
+                Variant::from_variant(
+                    &(*id, self.item_properties(*id), Vec::<Variant>::new()).to_variant(),
+                )
+            })
+            .collect();
+        (0i32, self.item_properties(0), children).to_variant()
+    }
+
+    fn clicked(&self, id: i32, event: &str) {
+        if event != "clicked" {
+            return;
+        }
+        if let Some((_, action)) = ACTIONS.iter().find(|(x, _)| *x == id) {
+            (self.activate)(*action);
+        }
+    }
+
+    fn call(
+        &self,
+        interface: &str,
+        method: &str,
+        parameters: &Variant,
+        invocation: gio::DBusMethodInvocation,
+    ) {
+        let reply = match (interface, method) {
+            (ITEM, "Activate") => {
+                (self.activate)(TrayAction::Show);
+                None
+            }
+            (ITEM, "SecondaryActivate") => {
+                (self.activate)(TrayAction::Toggle);
+                None
+            }
+            (ITEM, _) => None,
+            (MENU, "GetLayout") => Some((self.state.borrow().revision, self.layout()).to_variant()),
+            (MENU, "GetGroupProperties") => {
+                let ids: Vec<i32> = parameters.child_value(0).get().unwrap_or_default();
+                let items: Vec<(i32, HashMap<String, Variant>)> = ids
+                    .into_iter()
+                    .map(|id| (id, self.item_properties(id)))
+                    .collect();
+                Some((items,).to_variant())
+            }
+            (MENU, "GetProperty") => {
+                let id: i32 = parameters.child_value(0).get().unwrap_or_default();
+                let name = parameters
+                    .child_value(1)
+                    .str()
+                    .unwrap_or_default()
+                    .to_string();
+                let value = self
+                    .item_properties(id)
+                    .remove(&name)
+                    .unwrap_or_else(|| "".to_variant());
+                Some((value,).to_variant())
+            }
+            (MENU, "Event") => {
+                let id: i32 = parameters.child_value(0).get().unwrap_or_default();
+                self.clicked(id, parameters.child_value(1).str().unwrap_or_default());
+                None
+            }
+            (MENU, "EventGroup") => {
+                let events = parameters.child_value(0);
+                for i in 0..events.n_children() {
+                    let event = events.child_value(i);
+                    let id: i32 = event.child_value(0).get().unwrap_or_default();
+                    self.clicked(id, event.child_value(1).str().unwrap_or_default());
+                }
+                Some((Vec::<i32>::new(),).to_variant())
+            }
+            (MENU, "AboutToShow") => Some((false,).to_variant()),
+            (MENU, "AboutToShowGroup") => Some((Vec::<i32>::new(), Vec::<i32>::new()).to_variant()),
+            _ => None,
+        };
+        invocation.return_value(reply.as_ref());
+    }
+}
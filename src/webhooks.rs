@@ -0,0 +1,159 @@
+//! HTTP POST webhooks for timer events.
+//!
+//! Every `hooks::Payload` is queued once per URL in `webhooks` and posted as JSON by a
+//! background thread, failed deliveries are retried with a growing delay. The queue is kept
+//! in the `webhook-queue` file, so deliveries survive restarts.
+use crate::cfg;
+use crate::hooks::Payload;
+use chrono::{DateTime, Local};
+use lazy_static::lazy_static;
+use serde_derive::{Deserialize, Serialize};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+const QUEUE: &str = "webhook-queue";
+/// Deliveries are dropped after this many failed attempts, about 20 minutes in
+const MAX_ATTEMPTS: u32 = 8;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Delivery {
+    url: String,
+    /// The JSON payload
+    body: String,
+    attempts: u32,
+    next_attempt: DateTime<Local>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Queue {
+    deliveries: Vec<Delivery>,
+}
+
+lazy_static! {
+    static ref PENDING: Mutex<Vec<Delivery>> = Mutex::new(load());
+    /// Wakes the delivery thread up when something was queued
+    static ref QUEUED: Condvar = Condvar::new();
+}
+
+fn load() -> Vec<Delivery> {
+    match confy::load::<Queue>("flowtime", Some(QUEUE)) {
+        Ok(queue) => queue.deliveries,
+        Err(e) => {
+            eprintln!("flowtime: dropping the unreadable webhook queue: {}", e);
+            vec![]
+        }
+    }
+}
+
+fn save(deliveries: &[Delivery]) {
+    let queue = Queue {
+        deliveries: deliveries.to_vec(),
+    };
+    if let Err(e) = confy::store("flowtime", Some(QUEUE), queue) {
+        eprintln!("flowtime: failed to save the webhook queue: {}", e);
+    }
+}
+
+fn post(url: &str, body: &str) -> anyhow::Result<()> {
+    ureq::post(url)
+        .timeout(TIMEOUT)
+        .set("Content-Type", "application/json")
+        .send_string(body)?;
+    Ok(())
+}
+
+/// Seconds until the next attempt after `attempts` failed ones
+fn backoff(attempts: u32) -> i64 {
+    10 * 2i64.pow(attempts.saturating_sub(1).min(8))
+}
+
+/// Queues `payload` for every configured URL
+pub fn send(payload: &Payload) {
+    let urls = cfg!().webhooks;
+    if urls.is_empty() {
+        return;
+    }
+    let body = serde_json::to_string(payload).unwrap();
+    let mut pending = PENDING.lock().unwrap();
+    pending.extend(urls.into_iter().map(|url| Delivery {
+        url,
+        body: body.clone(),
+        attempts: 0,
+        next_attempt: Local::now(),
+    }));
+    save(&pending);
+    QUEUED.notify_one();
+}
+
+/// Delivers the queue in the background, starting with what was left from the last run
+pub fn start() {
+    std::thread::spawn(|| loop {
+        let delivery = {
+            let mut pending = PENDING.lock().unwrap();
+            loop {
+                let now = Local::now();
+                if let Some(x) = pending.iter().find(|x| x.next_attempt <= now) {
+                    break x.clone();
+                }
+                let wait = pending
+                    .iter()
+                    .filter_map(|x| (x.next_attempt - now).to_std().ok())
+                    .min()
+                    .unwrap_or(Duration::from_secs(3600));
+                pending = QUEUED.wait_timeout(pending, wait).unwrap().0;
+            }
+        };
+        // the delivery stays queued while it is sent, in case the app quits meanwhile
+        let result = post(&delivery.url, &delivery.body);
+        let mut pending = PENDING.lock().unwrap();
+        let i = match pending
+            .iter()
+            .position(|x| x.url == delivery.url && x.body == delivery.body)
+        {
+            Some(x) => x,
+            None => continue,
+        };
+        match result {
+            Ok(()) => {
+                pending.remove(i);
+            }
+            Err(e) if pending[i].attempts + 1 >= MAX_ATTEMPTS => {
+                eprintln!("flowtime: giving up on webhook {}: {}", delivery.url, e);
+                pending.remove(i);
+            }
+            Err(e) => {
+                let delivery = &mut pending[i];
+                delivery.attempts += 1;
+                delivery.next_attempt =
+                    Local::now() + chrono::Duration::seconds(backoff(delivery.attempts));
+                eprintln!(
+                    "flowtime: webhook {} failed, retrying at {}: {}",
+                    delivery.url,
+                    delivery.next_attempt.format("%H:%M:%S"),
+                    e
+                );
+            }
+        }
+        save(&pending);
+    });
+}
+
+/// Posts a `test` event to each of `urls` right away, returns how that went
+pub fn test(urls: &[String]) -> String {
+    if urls.is_empty() {
+        return "No webhook URLs".to_string();
+    }
+    let body = serde_json::json!({
+        "event": "test",
+        "time": Local::now(),
+    })
+    .to_string();
+    urls.iter()
+        .map(|url| match post(url, &body) {
+            Ok(()) => format!("{url}: delivered"),
+            Err(e) => format!("{url}: {e}"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}